@@ -1,10 +1,18 @@
 extern crate cc;
+extern crate pkg_config;
 extern crate verilator;
 
 use std::env;
+use std::fs;
 use std::path::PathBuf;
-use verilator::find_verilator_root;
-use verilator::verilator_version;
+use verilator::{has_verilated_context, Capabilities, InstallError, VerilatorInstall, Version};
+
+/// The features which select parts of the runtime.
+const PARTS: &[&str] = &["coverage", "dpi", "save", "vcd", "vpi", "fst", "threads"];
+
+/// The parts of the runtime selected by the default features, assumed of a
+/// prebuilt runtime which does not list its own.
+const DEFAULT_PARTS: &[&str] = &["coverage", "dpi", "save", "vcd", "vpi"];
 
 fn getenv_unwrap(v: &str) -> String {
     match env::var(v) {
@@ -17,67 +25,363 @@ fn fail(s: &str) -> ! {
     panic!("\n{}\n\nbuild script failed, must exit now", s)
 }
 
+/// A prebuilt runtime library to link instead of compiling the runtime
+/// sources.
+struct Prebuilt {
+    /// Where the library is described, for error messages.
+    source: String,
+    link_paths: Vec<PathBuf>,
+    libs: Vec<String>,
+    include_paths: Vec<PathBuf>,
+    /// The verilator version the library was compiled from, if known.
+    version: Option<Version>,
+    /// The parts of the runtime the library includes, named as the features
+    /// of this crate, if known.
+    parts: Option<Vec<String>>,
+}
+
+/// Find a prebuilt runtime in `${VERILATED_LIB_DIR}`, or through pkg-config
+/// if `verilator.pc` lists any libraries. Stock Verilator installs only
+/// describe the include paths.
+///
+/// The library is described by `${VERILATED_LIB_DIR}/verilated.cfg`, or by
+/// the version and the `features` variable of `verilator.pc`.
+fn find_prebuilt() -> Option<Prebuilt> {
+    println!("cargo:rerun-if-env-changed=VERILATED_LIB_DIR");
+    if let Some(dir) = env::var_os("VERILATED_LIB_DIR") {
+        let dir = PathBuf::from(dir);
+        let mut prebuilt = Prebuilt {
+            source: dir.join("verilated.cfg").display().to_string(),
+            link_paths: vec![dir.clone()],
+            libs: vec!["verilated".to_string()],
+            include_paths: Vec::new(),
+            version: None,
+            parts: None,
+        };
+        read_cfg(&mut prebuilt);
+        return Some(prebuilt);
+    }
+    match pkg_config::Config::new()
+        .cargo_metadata(false)
+        .env_metadata(true)
+        .probe("verilator")
+    {
+        Ok(lib) if !lib.libs.is_empty() => Some(Prebuilt {
+            source: "verilator.pc".to_string(),
+            link_paths: lib.link_paths,
+            libs: lib.libs,
+            include_paths: lib.include_paths,
+            version: lib.version.parse().ok(),
+            parts: pkg_config::get_variable("verilator", "features")
+                .ok()
+                .filter(|parts| !parts.is_empty())
+                .map(|parts| parts.split_whitespace().map(|p| p.to_string()).collect()),
+        }),
+        _ => None,
+    }
+}
+
+/// Read the `key = value` lines of `verilated.cfg`, if it exists:
+///
+/// ```text
+/// version = 5.020
+/// include = /opt/verilator/include /opt/verilator/include/vltstd
+/// features = coverage vcd threads
+/// ```
+fn read_cfg(prebuilt: &mut Prebuilt) {
+    println!("cargo:rerun-if-changed={}", prebuilt.source);
+    let content = match fs::read_to_string(&prebuilt.source) {
+        Ok(content) => content,
+        Err(..) => return,
+    };
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.find('=') {
+            Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+            None => fail(&format!(
+                "{}: expected `key = value`, found `{}`",
+                prebuilt.source, line
+            )),
+        };
+        match key {
+            "version" => match value.parse() {
+                Ok(version) => prebuilt.version = Some(version),
+                Err(e) => fail(&format!("{}: {}", prebuilt.source, e)),
+            },
+            "include" => {
+                prebuilt.include_paths = value.split_whitespace().map(PathBuf::from).collect()
+            }
+            "features" => {
+                prebuilt.parts = Some(value.split_whitespace().map(|p| p.to_string()).collect())
+            }
+            _ => fail(&format!("{}: unknown key `{}`", prebuilt.source, key)),
+        }
+    }
+}
+
 fn main() {
-    if let Some(root) = find_verilator_root() {
-        // cargo:rustc-cfg=KEY[="VALUE"]
-        let (major, minor) = verilator_version().unwrap();
-        println!("cargo:rustc-cfg=verilator_version=\"{}.{}\"", major, minor);
-        if major == 4 && minor >= 38 {
-            println!("cargo:rustc-cfg=verilator=\"flush_and_exit_cb\"");
+    let feature =
+        |name: &str| env::var_os(format!("CARGO_FEATURE_{}", name.to_ascii_uppercase())).is_some();
+
+    // A prebuilt runtime which gives its version and headers needs no
+    // verilator installation.
+    let prebuilt = find_prebuilt();
+    let standalone = prebuilt
+        .as_ref()
+        .is_some_and(|p| p.version.is_some() && !p.include_paths.is_empty());
+    let install = match VerilatorInstall::discover() {
+        Ok(install) => Some(install),
+        Err(InstallError::NotFound(..)) if standalone => None,
+        Err(InstallError::NotFound(..)) => fail("Failed to find `${VERILATOR_ROOT}`.  Please set `VERILATOR_ROOT` environment variable or ensure `verilator` is in `PATH`."),
+        Err(e) => fail(&e.to_string()),
+    };
+    let version = match (install.as_ref(), prebuilt.as_ref()) {
+        (Some(install), Some(&Prebuilt { version: Some(version), ref source, .. }))
+            if (version.major, version.minor) != (install.version.major, install.version.minor) =>
+        {
+            fail(&format!(
+                "the prebuilt runtime described by {} was compiled from verilator {}, but verilator {} is installed",
+                source, version, install.version
+            ))
+        }
+        (Some(install), _) => install.version,
+        (None, Some(&Prebuilt { version: Some(version), .. })) => version,
+        (None, _) => unreachable!(),
+    };
+
+    // The parts of the runtime linked, named as the features of this crate:
+    // those a prebuilt runtime lists, or else the default features, which a
+    // runtime built by verilator's makefiles includes. Only the shims need
+    // compiling when linking a prebuilt runtime, which must include every
+    // part selected by features.
+    let parts: Vec<String> = match prebuilt {
+        Some(Prebuilt {
+            parts: Some(ref parts),
+            ..
+        }) => parts.clone(),
+        Some(ref prebuilt) => {
+            let mut parts = DEFAULT_PARTS.to_vec();
+            if version.major >= 5 {
+                parts.push("threads");
+            }
+            println!(
+                "cargo:warning=the prebuilt runtime described by {} does not list its parts, assuming `{}`",
+                prebuilt.source,
+                parts.join(" ")
+            );
+            parts.iter().map(|p| p.to_string()).collect()
         }
+        None => PARTS
+            .iter()
+            .filter(|part| feature(part))
+            .map(|p| p.to_string())
+            .collect(),
+    };
+    let linked = |part: &str| parts.iter().any(|p| p == part);
+    if let Some(ref prebuilt) = prebuilt {
+        let missing: Vec<_> = PARTS
+            .iter()
+            .filter(|part| feature(part) && !linked(part))
+            .collect();
+        if !missing.is_empty() {
+            fail(&format!(
+                "the prebuilt runtime described by {} lacks {:?}, enabled by features of `verilated`",
+                prebuilt.source, missing
+            ));
+        }
+    }
+
+    let capabilities = match install {
+        Some(ref install) => install.capabilities,
+        // Without an installation, the prebuilt runtime decides what models
+        // can use.
+        None => Capabilities {
+            threads: version.major >= 5 || linked("threads"),
+            fst: linked("fst"),
+            timing: false,
+            context: has_verilated_context((version.major, version.minor)),
+        },
+    };
 
-        let include = root.join("include");
+    // cargo:rustc-cfg=KEY[="VALUE"]
+    let (major, minor) = (version.major, version.minor);
+    println!("cargo:rustc-cfg=verilator_version=\"{}.{}\"", major, minor);
+    if major > 4 || (major == 4 && minor >= 38) {
+        println!("cargo:rustc-cfg=verilator=\"flush_and_exit_cb\"");
+    }
+    let context = capabilities.context;
+    if context {
+        println!("cargo:rustc-cfg=verilator=\"context\"");
+    }
+
+    let mut target = getenv_unwrap("TARGET");
+    if target.ends_with("-darwin") {
+        target = target + "11";
+    }
 
-        let mut target = getenv_unwrap("TARGET");
-        if target.ends_with("-darwin") {
-            target = target + "11";
+    // Only compile the parts of the runtime selected by features.
+    let mut files = vec!["verilated.cpp"];
+    let coverage = feature("coverage");
+    if coverage {
+        files.push("verilated_cov.cpp");
+    }
+    let dpi = feature("dpi");
+    if dpi {
+        files.push("verilated_dpi.cpp");
+    }
+    let save = feature("save");
+    if save {
+        files.push("verilated_save.cpp");
+    }
+    let vcd = feature("vcd");
+    if vcd {
+        files.push("verilated_vcd_c.cpp");
+    }
+    let vpi = feature("vpi");
+    if vpi {
+        files.push("verilated_vpi.cpp");
+    }
+    // Verilator 5 always uses the threaded runtime, older versions only
+    // for models built with `--threads`.
+    let threads = feature("threads");
+    if threads && !capabilities.threads {
+        fail(&format!(
+            "the `threads` feature needs the multithreaded runtime, which verilator {} does not provide",
+            version
+        ));
+    }
+    if major >= 5 || threads {
+        files.push("verilated_threads.cpp");
+    }
+    if major >= 5 {
+        println!("cargo:rustc-cfg=verilator=\"context_threads\"");
+    }
+    // The FST writer and its compressors are #included by
+    // verilated_fst_c.cpp.
+    let fst = feature("fst");
+    if fst && !capabilities.fst {
+        fail(&format!(
+            "the `fst` feature needs the FST runtime, which verilator {} does not provide",
+            version
+        ));
+    }
+    if fst {
+        files.push("verilated_fst_c.cpp");
+    }
+
+    let mut cfg = cc::Build::new();
+    let tool = cfg.get_compiler();
+    cfg.cpp(true).target(&target);
+    if tool.is_like_clang() {
+        cfg.flag("-faligned-new")
+            .flag("-fbracket-depth=4096")
+            .flag("-Qunused-arguments")
+            .flag("-Wno-parentheses-equality")
+            .flag("-Wno-sign-compare")
+            .flag("-Wno-uninitialized")
+            .flag("-Wno-unused-parameter")
+            .flag("-Wno-unused-variable")
+            .flag("-Wno-shadow");
+    }
+    if tool.is_like_gnu() {
+        cfg.flag("-std=gnu++17")
+            .flag("-faligned-new")
+            .flag("-Wno-bool-operation")
+            .flag("-Wno-sign-compare")
+            .flag("-Wno-uninitialized")
+            .flag("-Wno-unused-but-set-variable")
+            .flag("-Wno-unused-parameter")
+            .flag("-Wno-unused-variable")
+            .flag("-Wno-shadow");
+    }
+    cfg.define("VERILATOR_VERSION_MAJOR", format!("{}", major).as_str())
+        .define("VERILATOR_VERSION_MINOR", format!("{}", minor).as_str());
+    if coverage {
+        cfg.file("src/verilatedcov_shim.cpp");
+    }
+    if vcd {
+        cfg.file("src/verilatedvcdc_shim.cpp");
+    }
+    if fst {
+        cfg.file("src/verilatedfstc_shim.cpp");
+        println!("cargo:rustc-link-lib=z");
+    }
+    // Definitions that models linked against the runtime must be
+    // compiled with too. The `VM_*` variables are those verilator writes to
+    // each model's `_classes.mk`; `dpi`, `save` and `vpi` have none.
+    let flag = |on: bool| Some(if on { "1" } else { "0" });
+    let (vcd_linked, fst_linked) = (linked("vcd"), linked("fst"));
+    let mut defines = vec![
+        ("VM_COVERAGE", flag(linked("coverage"))),
+        ("VM_SC", flag(false)),
+        ("VM_TRACE", flag(vcd_linked || fst_linked)),
+        ("VM_TRACE_VCD", flag(vcd_linked)),
+        ("VM_TRACE_FST", flag(fst_linked)),
+    ];
+    let threaded = major >= 5 || linked("threads");
+    if threaded && major < 5 {
+        defines.push(("VL_THREADED", Some("1")));
+    }
+    if context {
+        // Take $time from VerilatedContext rather than a user supplied
+        // sc_time_stamp().
+        defines.push(("VL_TIME_CONTEXT", None));
+        cfg.file("src/verilatedcontext_shim.cpp");
+    }
+    for &(var, val) in &defines {
+        cfg.define(var, val);
+    }
+    match prebuilt {
+        Some(ref prebuilt) => {
+            cfg.includes(&prebuilt.include_paths);
+        }
+        None => {
+            let include = install.as_ref().unwrap().root.join("include");
+            cfg.files(files.iter().map(|p| include.join(p)));
         }
+    }
+    if let Some(ref install) = install {
+        let include = install.root.join("include");
+        cfg.include(&include).include(include.join("vltstd"));
+    }
+    cfg.file("src/verilated_shim.cpp");
+    cfg.compile("verilated_all");
 
-        let files = vec![
-            "verilated.cpp",
-            "verilated_cov.cpp",
-            "verilated_dpi.cpp",
-            "verilated_save.cpp",
-            "verilated_vcd_c.cpp",
-            "verilated_vpi.cpp",
-        ];
-
-        let files: Vec<PathBuf> = files.iter().map(|p| include.join(p)).collect();
-
-        let mut cfg = cc::Build::new();
-        let tool = cfg.get_compiler();
-        cfg.cpp(true).target(&target);
-        if tool.is_like_clang() {
-            cfg.flag("-faligned-new")
-                .flag("-fbracket-depth=4096")
-                .flag("-Qunused-arguments")
-                .flag("-Wno-parentheses-equality")
-                .flag("-Wno-sign-compare")
-                .flag("-Wno-uninitialized")
-                .flag("-Wno-unused-parameter")
-                .flag("-Wno-unused-variable")
-                .flag("-Wno-shadow");
+    // Link the prebuilt runtime after the shims which use it.
+    if let Some(prebuilt) = prebuilt {
+        for path in &prebuilt.link_paths {
+            println!("cargo:rustc-link-search=native={}", path.display());
         }
-        if tool.is_like_gnu() {
-            cfg.flag("-std=gnu++17")
-                .flag("-faligned-new")
-                .flag("-Wno-bool-operation")
-                .flag("-Wno-sign-compare")
-                .flag("-Wno-uninitialized")
-                .flag("-Wno-unused-but-set-variable")
-                .flag("-Wno-unused-parameter")
-                .flag("-Wno-unused-variable")
-                .flag("-Wno-shadow");
+        for lib in &prebuilt.libs {
+            println!("cargo:rustc-link-lib={}", lib);
         }
-        cfg.define("VERILATOR_VERSION_MAJOR", format!("{}", major).as_str())
-            .define("VERILATOR_VERSION_MINOR", format!("{}", minor).as_str());
-        cfg.include(&include)
-            .include(include.join("vltstd"))
-            .files(files)
-            .file("src/verilated_shim.cpp")
-            .file("src/verilatedvcdc_shim.cpp");
-        cfg.compile("verilated_all");
-    } else {
-        fail("Failed to find `${VERILATOR_ROOT}`.  Please set `VERILATOR_ROOT` environment variable or ensure `verilator` is in `PATH`.");
     }
+    if threaded && !target.contains("windows") {
+        println!("cargo:rustc-link-lib=pthread");
+    }
+
+    // cargo:KEY=VALUE, seen by dependent build scripts as
+    // DEP_VERILATED_KEY
+    if let Some(ref install) = install {
+        println!("cargo:root={}", install.root.display());
+    }
+    println!("cargo:version={}", version);
+    // The parts linked, which may be more than features select for a
+    // prebuilt runtime, and the features, which select the Rust modules.
+    println!("cargo:threads={}", threaded as u8);
+    for part in &["coverage", "dpi", "save", "vcd", "vpi", "fst"] {
+        println!("cargo:{}={}", part, linked(part) as u8);
+    }
+    let features: Vec<&str> = PARTS.iter().cloned().filter(|part| feature(part)).collect();
+    println!("cargo:features={}", features.join(" "));
+    let defines: Vec<String> = defines
+        .iter()
+        .map(|&(var, val)| match val {
+            Some(val) => format!("{}={}", var, val),
+            None => var.to_string(),
+        })
+        .collect();
+    println!("cargo:defines={}", defines.join(" "));
 }
@@ -0,0 +1,181 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+
+use api::RandomMode;
+
+pub enum ContextC {}
+
+mod ffi {
+    use super::ContextC;
+    use std::os::raw::{c_char, c_int, c_uint};
+
+    extern "C" {
+        pub fn verilatedcontext_new() -> *mut ContextC;
+        pub fn verilatedcontext_delete(ctx: *mut ContextC);
+        pub fn verilatedcontext_time(ctx: *mut ContextC) -> u64;
+        pub fn verilatedcontext_set_time(ctx: *mut ContextC, value: u64);
+        pub fn verilatedcontext_time_inc(ctx: *mut ContextC, add: u64);
+        pub fn verilatedcontext_command_args(
+            ctx: *mut ContextC,
+            argc: c_int,
+            argv: *const *const c_char,
+        );
+        pub fn verilatedcontext_command_args_plus_match(
+            ctx: *mut ContextC,
+            prefixp: *const c_char,
+        ) -> *const c_char;
+        pub fn verilatedcontext_set_rand_reset(ctx: *mut ContextC, val: c_int);
+        pub fn verilatedcontext_rand_reset(ctx: *mut ContextC) -> c_int;
+        pub fn verilatedcontext_set_rand_seed(ctx: *mut ContextC, val: c_int);
+        pub fn verilatedcontext_rand_seed(ctx: *mut ContextC) -> c_int;
+        pub fn verilatedcontext_set_got_finish(ctx: *mut ContextC, flag: c_int);
+        pub fn verilatedcontext_got_finish(ctx: *mut ContextC) -> c_int;
+        pub fn verilatedcontext_trace_ever_on(ctx: *mut ContextC, flag: c_int);
+        pub fn verilatedcontext_set_assert_on(ctx: *mut ContextC, flag: c_int);
+        pub fn verilatedcontext_assert_on(ctx: *mut ContextC) -> c_int;
+    }
+
+    #[cfg(verilator = "context_threads")]
+    extern "C" {
+        pub fn verilatedcontext_set_threads(ctx: *mut ContextC, n: c_uint);
+        pub fn verilatedcontext_threads(ctx: *mut ContextC) -> c_uint;
+    }
+}
+
+/// Simulation state shared by the models constructed against it, wrapping
+/// `VerilatedContext`.
+///
+/// Each context owns its own simulation time, `$plusargs`, random seed and
+/// `$finish` flag, so independent simulations don't share global state.
+pub struct Context(pub *mut ContextC);
+
+impl Context {
+    /// Return current simulation time
+    pub fn time(&self) -> u64 {
+        unsafe { ffi::verilatedcontext_time(self.0) }
+    }
+
+    /// Set current simulation time
+    pub fn set_time(&self, time: u64) {
+        unsafe { ffi::verilatedcontext_set_time(self.0, time) }
+    }
+
+    /// Advance simulation time
+    pub fn time_inc(&self, add: u64) {
+        unsafe { ffi::verilatedcontext_time_inc(self.0, add) }
+    }
+
+    /// Record command line arguments, for retrieval by $test$plusargs/$value$plusargs
+    pub fn command_args(&self, args: Vec<CString>) {
+        let args: Vec<*const c_char> = args.iter().map(|a| a.as_ptr()).collect();
+        unsafe {
+            ffi::verilatedcontext_command_args(self.0, args.len() as c_int, args.as_ptr());
+        }
+    }
+
+    /// Match plusargs with a given prefix, returning the whole matching
+    /// argument, such as `+verbose=1` for the prefix `verbose`.
+    pub fn plus_match(&self, prefix: &str) -> Option<String> {
+        let prefix = CString::new(prefix).ok()?;
+        let matched = unsafe {
+            let matched = ffi::verilatedcontext_command_args_plus_match(self.0, prefix.as_ptr());
+            if matched.is_null() {
+                return None;
+            }
+            CStr::from_ptr(matched)
+        };
+        let matched = matched.to_string_lossy();
+        if matched.is_empty() {
+            None
+        } else {
+            Some(matched.into_owned())
+        }
+    }
+
+    /// Select initial value of otherwise uninitialized signals.
+    pub fn set_rand_reset(&self, val: RandomMode) {
+        let val = match val {
+            RandomMode::AllZeros => 0,
+            RandomMode::AllBits => 1,
+            RandomMode::Randomize => 2,
+        };
+        unsafe { ffi::verilatedcontext_set_rand_reset(self.0, val) }
+    }
+
+    /// Return randReset value
+    pub fn rand_reset(&self) -> RandomMode {
+        match unsafe { ffi::verilatedcontext_rand_reset(self.0) } {
+            0 => RandomMode::AllZeros,
+            1 => RandomMode::AllBits,
+            _ => RandomMode::Randomize,
+        }
+    }
+
+    /// Set the random seed, or 0 to seed from the time of day
+    pub fn set_rand_seed(&self, seed: i32) {
+        unsafe { ffi::verilatedcontext_set_rand_seed(self.0, seed) }
+    }
+
+    /// Return the random seed
+    pub fn rand_seed(&self) -> i32 {
+        unsafe { ffi::verilatedcontext_rand_seed(self.0) }
+    }
+
+    /// Did the simulation $finish?
+    pub fn set_got_finish(&self, finish: bool) {
+        unsafe { ffi::verilatedcontext_set_got_finish(self.0, finish as _) }
+    }
+
+    /// Return if got a $finish
+    pub fn got_finish(&self) -> bool {
+        unsafe { ffi::verilatedcontext_got_finish(self.0) != 0 }
+    }
+
+    /// Allow traces to at some point be enabled (disables some optimizations)
+    pub fn trace_ever_on(&self, on: bool) {
+        unsafe { ffi::verilatedcontext_trace_ever_on(self.0, on as _) }
+    }
+
+    /// Enable/disable assertions
+    pub fn set_assert_on(&self, on: bool) {
+        unsafe { ffi::verilatedcontext_set_assert_on(self.0, on as _) }
+    }
+
+    pub fn assert_on(&self) -> bool {
+        unsafe { ffi::verilatedcontext_assert_on(self.0) != 0 }
+    }
+
+    /// Set the number of threads used to evaluate models constructed against
+    /// this context. Must be called before any model is constructed.
+    ///
+    /// Only Verilator 5 can change the threads at runtime, so `with_threads`
+    /// rejects older versions.
+    #[cfg(verilator = "context_threads")]
+    pub fn set_threads(&self, n: u32) {
+        unsafe { ffi::verilatedcontext_set_threads(self.0, n) }
+    }
+
+    /// Return the number of threads used to evaluate models
+    #[cfg(verilator = "context_threads")]
+    pub fn threads(&self) -> u32 {
+        unsafe { ffi::verilatedcontext_threads(self.0) }
+    }
+}
+
+impl Default for Context {
+    fn default() -> Context {
+        let ptr = unsafe { ffi::verilatedcontext_new() };
+        if ptr.is_null() {
+            panic!("Failed to allocate VerilatedContext");
+        }
+        Context(ptr)
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::verilatedcontext_delete(self.0);
+        }
+    }
+}
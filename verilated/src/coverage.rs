@@ -0,0 +1,32 @@
+//! Coverage data collected by models verilated with `--coverage`.
+//!
+//! From Verilator 4.200 coverage is kept per context, and these functions act
+//! on the calling thread's context, which is the default global context
+//! unless a model constructed with another one is being evaluated.
+
+use std::io;
+use std::path::Path;
+
+use cstr;
+
+mod ffi {
+    use std::os::raw::c_char;
+
+    extern "C" {
+        pub fn verilatedcov_write(filename: *const c_char);
+        pub fn verilatedcov_zero();
+    }
+}
+
+/// Write the coverage collected so far to `path`, in the format read by
+/// `verilator_coverage`.
+pub fn write<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = cstr(path.as_ref())?;
+    unsafe { ffi::verilatedcov_write(path.as_ptr()) }
+    Ok(())
+}
+
+/// Reset the count of every coverage point to zero.
+pub fn zero() {
+    unsafe { ffi::verilatedcov_zero() }
+}
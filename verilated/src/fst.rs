@@ -0,0 +1,74 @@
+use std::ffi::{CStr, CString};
+use std::io;
+use std::path::Path;
+
+use cstr;
+
+pub enum FstC {}
+
+mod ffi {
+    use super::FstC;
+    use std::os::raw::{c_char, c_int};
+
+    extern "C" {
+        pub fn verilatedfstc_new() -> *mut FstC;
+        pub fn verilatedfstc_delete(fst: *mut FstC);
+        pub fn verilatedfstc_is_open(fst: *mut FstC) -> c_int;
+        pub fn verilatedfstc_open(fst: *mut FstC, filename: *const c_char);
+        pub fn verilatedfstc_close(fst: *mut FstC);
+        pub fn verilatedfstc_flush(fst: *mut FstC);
+        pub fn verilatedfstc_dump(fst: *mut FstC, timeui: u64);
+        pub fn verilatedfstc_set_time_unit(fst: *mut FstC, unit: *const c_char);
+        pub fn verilatedfstc_set_time_resolution(fst: *mut FstC, unit: *const c_char);
+    }
+}
+
+pub struct Fst(pub *mut FstC);
+
+impl Fst {
+    fn _open(&mut self, path: &CStr) -> io::Result<()> {
+        unsafe {
+            ffi::verilatedfstc_open(self.0, path.as_ptr());
+            if ffi::verilatedfstc_is_open(self.0) == 0 {
+                return Err(io::ErrorKind::Other.into());
+            }
+            let time_unit = CString::new("1ns").unwrap();
+            ffi::verilatedfstc_set_time_unit(self.0, time_unit.as_ptr() as *const _);
+            ffi::verilatedfstc_set_time_resolution(self.0, time_unit.as_ptr() as *const _);
+        }
+
+        Ok(())
+    }
+
+    pub fn open<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let path = cstr(path.as_ref())?;
+        self._open(&path)
+    }
+
+    pub fn flush(&mut self) {
+        unsafe { ffi::verilatedfstc_flush(self.0) }
+    }
+
+    pub fn dump(&mut self, nanos: u64) {
+        unsafe { ffi::verilatedfstc_dump(self.0, nanos) }
+    }
+}
+
+impl Default for Fst {
+    fn default() -> Fst {
+        let ptr = unsafe { ffi::verilatedfstc_new() };
+        if ptr.is_null() {
+            panic!("Failed to allocate VerilatedFstC");
+        }
+        Fst(ptr)
+    }
+}
+
+impl Drop for Fst {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::verilatedfstc_close(self.0);
+            ffi::verilatedfstc_delete(self.0);
+        }
+    }
+}
@@ -1,4 +1,36 @@
 pub mod api;
+#[cfg(verilator = "context")]
+pub mod context;
+#[cfg(feature = "coverage")]
+pub mod coverage;
+#[cfg(feature = "fst")]
+pub mod fst;
+#[cfg(feature = "vcd")]
 pub mod vcd;
 
 pub use api::*;
+
+#[cfg(any(feature = "coverage", feature = "fst", feature = "vcd"))]
+use std::ffi::CString;
+#[cfg(any(feature = "coverage", feature = "fst", feature = "vcd"))]
+use std::io;
+#[cfg(any(feature = "coverage", feature = "fst", feature = "vcd"))]
+use std::path::Path;
+
+#[cfg(all(unix, any(feature = "coverage", feature = "fst", feature = "vcd")))]
+pub(crate) fn cstr(path: &Path) -> io::Result<CString> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(CString::new(path.as_os_str().as_bytes())?)
+}
+
+#[cfg(all(not(unix), any(feature = "coverage", feature = "fst", feature = "vcd")))]
+pub(crate) fn cstr(path: &Path) -> io::Result<CString> {
+    Ok(CString::new(
+        path.to_str()
+            .ok_or(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("path is not valid utf-8"),
+            ))?
+            .as_bytes(),
+    )?)
+}
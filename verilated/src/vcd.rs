@@ -2,6 +2,8 @@ use std::ffi::{CStr, CString};
 use std::io;
 use std::path::Path;
 
+use cstr;
+
 pub enum VcdC {}
 
 mod ffi {
@@ -23,24 +25,6 @@ mod ffi {
     }
 }
 
-#[cfg(unix)]
-fn cstr(path: &Path) -> io::Result<CString> {
-    use std::os::unix::ffi::OsStrExt;
-    Ok(CString::new(path.as_os_str().as_bytes())?)
-}
-
-#[cfg(not(unix))]
-fn cstr(path: &Path) -> io::Result<CString> {
-    Ok(CString::new(
-        path.to_str()
-            .ok_or(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("path is not valid utf-8"),
-            ))?
-            .as_bytes(),
-    )?)
-}
-
 pub struct Vcd(pub *mut VcdC);
 
 impl Vcd {
@@ -0,0 +1,117 @@
+// -*- mode: C++; c-file-style: "cc-mode" -*-
+/// \file
+/// \brief VerilatedContext Shim: Expose C++ interface as C functions.
+
+#include <verilated.h>
+
+// METHODS - User called
+extern "C" {
+
+VerilatedContext*
+verilatedcontext_new() {
+  return new VerilatedContext();
+}
+
+void
+verilatedcontext_delete(VerilatedContext* ctx) {
+  delete ctx;
+}
+
+/// Return current simulation time
+vluint64_t
+verilatedcontext_time(VerilatedContext* ctx) {
+  return ctx->time();
+}
+
+/// Set current simulation time
+void
+verilatedcontext_set_time(VerilatedContext* ctx, vluint64_t value) {
+  ctx->time(value);
+}
+
+/// Advance simulation time
+void
+verilatedcontext_time_inc(VerilatedContext* ctx, vluint64_t add) {
+  ctx->timeInc(add);
+}
+
+/// Record command line arguments, for retrieval by $test$plusargs/$value$plusargs
+void
+verilatedcontext_command_args(VerilatedContext* ctx, int argc, const char** argv) {
+  ctx->commandArgs(argc, argv);
+}
+
+/// Match plusargs with a given prefix. Returns static char* valid only for a single call
+const char*
+verilatedcontext_command_args_plus_match(VerilatedContext* ctx, const char* prefixp) {
+  return ctx->commandArgsPlusMatch(prefixp);
+}
+
+/// Select initial value of otherwise uninitialized signals.
+void
+verilatedcontext_set_rand_reset(VerilatedContext* ctx, int val) {
+  ctx->randReset(val);
+}
+
+/// Return randReset value
+int
+verilatedcontext_rand_reset(VerilatedContext* ctx) {
+  return ctx->randReset();
+}
+
+/// Set random seed, 0 = use time of day
+void
+verilatedcontext_set_rand_seed(VerilatedContext* ctx, int val) {
+  ctx->randSeed(val);
+}
+
+/// Return random seed
+int
+verilatedcontext_rand_seed(VerilatedContext* ctx) {
+  return ctx->randSeed();
+}
+
+/// Did the simulation $finish?
+void
+verilatedcontext_set_got_finish(VerilatedContext* ctx, int flag) {
+  ctx->gotFinish(flag != 0);
+}
+
+/// Return if got a $finish
+int
+verilatedcontext_got_finish(VerilatedContext* ctx) {
+  return ctx->gotFinish() ? 1 : 0;
+}
+
+/// Allow traces to at some point be enabled (disables some optimizations)
+void
+verilatedcontext_trace_ever_on(VerilatedContext* ctx, int flag) {
+  ctx->traceEverOn(flag != 0);
+}
+
+/// Enable/disable assertions
+void
+verilatedcontext_set_assert_on(VerilatedContext* ctx, int flag) {
+  ctx->assertOn(flag != 0);
+}
+
+int
+verilatedcontext_assert_on(VerilatedContext* ctx) {
+  return ctx->assertOn() ? 1 : 0;
+}
+
+#if VERILATOR_VERSION_MAJOR >= 5
+/// Set number of threads used by models, before any model is constructed
+void
+verilatedcontext_set_threads(VerilatedContext* ctx, unsigned n) {
+  ctx->threads(n);
+}
+
+/// Return number of threads used by models
+unsigned
+verilatedcontext_threads(VerilatedContext* ctx) {
+  return ctx->threads();
+}
+#endif // VERILATOR_VERSION_MAJOR >= 5
+
+}
@@ -0,0 +1,36 @@
+// -*- mode: C++; c-file-style: "cc-mode" -*-
+/// \file
+/// \brief VerilatedCov Shim: Expose C++ interface as C functions.
+
+#include <verilated.h>
+#include <verilated_cov.h>
+
+#if VERILATOR_VERSION_MAJOR > 4 || (VERILATOR_VERSION_MAJOR == 4 && VERILATOR_VERSION_MINOR >= 200)
+// Coverage is kept per VerilatedContext; use the calling thread's.
+#define verilatedcov_covp() (Verilated::threadContextp()->coveragep())
+#endif
+
+// METHODS - User called
+extern "C" {
+
+/// Write all coverage data to a file
+void
+verilatedcov_write(const char* filename) {
+#ifdef verilatedcov_covp
+  verilatedcov_covp()->write(filename);
+#else
+  VerilatedCov::write(filename);
+#endif
+}
+
+/// Zero coverage points
+void
+verilatedcov_zero() {
+#ifdef verilatedcov_covp
+  verilatedcov_covp()->zero();
+#else
+  VerilatedCov::zero();
+#endif
+}
+
+}
@@ -0,0 +1,63 @@
+// -*- mode: C++; c-file-style: "cc-mode" -*-
+/// \file
+/// \brief VerilatedFstC Shim: Expose C++ interface as C functions.
+
+#include <verilated_fst_c.h>
+
+// METHODS - User called
+extern "C" {
+
+VerilatedFstC*
+verilatedfstc_new() {
+  return new VerilatedFstC();
+}
+
+void
+verilatedfstc_delete(VerilatedFstC* fst) {
+  delete fst;
+}
+
+// ACCESSORS
+/// Is file open?
+int
+verilatedfstc_is_open(VerilatedFstC* fst) {
+  return fst->isOpen() ? 1 : 0;
+}
+
+// METHODS
+/// Open a new FST file
+void
+verilatedfstc_open(VerilatedFstC* fst, const char* filename) {
+  fst->open(filename);
+}
+
+/// Close dump
+void
+verilatedfstc_close(VerilatedFstC* fst) {
+  fst->close();
+}
+
+/// Flush dump
+void
+verilatedfstc_flush(VerilatedFstC* fst) {
+  fst->flush();
+}
+
+/// Write one cycle of dump data
+void verilatedfstc_dump(VerilatedFstC* fst, vluint64_t timeui) {
+  fst->dump(timeui);
+}
+
+/// Set time units (s/ms, defaults to ns)
+void
+verilatedfstc_set_time_unit(VerilatedFstC* fst, const char* unit) {
+  fst->set_time_unit(unit);
+}
+
+/// Set time resolution (s/ms, defaults to ns)
+void
+verilatedfstc_set_time_resolution(VerilatedFstC* fst, const char* unit) {
+  fst->set_time_resolution(unit);
+}
+
+}
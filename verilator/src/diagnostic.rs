@@ -0,0 +1,164 @@
+use regex::Regex;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Severity of a message reported by verilator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A single `%Warning-*` or `%Error-*` message reported by verilator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The message code, such as `WIDTH`. Plain `%Error:` messages have none.
+    pub code: Option<String>,
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub col: Option<u32>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn parse_line(re: &Regex, line: &str) -> Option<Diagnostic> {
+        let captures = re.captures(line.trim_end())?;
+        let severity = if &captures[1] == "Warning" {
+            Severity::Warning
+        } else {
+            Severity::Error
+        };
+        Some(Diagnostic {
+            code: captures.get(2).map(|m| m.as_str().to_string()),
+            severity,
+            file: captures.get(3).map(|m| PathBuf::from(m.as_str())),
+            line: captures.get(4).and_then(|m| m.as_str().parse().ok()),
+            col: captures.get(5).and_then(|m| m.as_str().parse().ok()),
+            message: captures[6].to_string(),
+        })
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}:", file.display())?;
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+            }
+            if let Some(col) = self.col {
+                write!(f, "{}:", col)?;
+            }
+            write!(f, " ")?;
+        }
+        match self.severity {
+            Severity::Warning => write!(f, "%Warning")?,
+            Severity::Error => write!(f, "%Error")?,
+        }
+        if let Some(ref code) = self.code {
+            write!(f, "-{}", code)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Parse every warning and error in the output of a verilator run.
+///
+/// Continuation lines, such as `... In instance` notes and source excerpts,
+/// are skipped, as is the `%Error: Exiting due to ...` trailer summing up
+/// the others.
+pub fn parse(output: &str) -> Vec<Diagnostic> {
+    let re =
+        Regex::new(r"^%(Warning|Error)(?:-([A-Za-z0-9_]+))?: (?:(\S+?):(\d+):(?:(\d+):)? )?(.*)$")
+            .expect("Failed to create diagnostic regex");
+    output
+        .lines()
+        .filter_map(|line| Diagnostic::parse_line(&re, line))
+        .filter(|d| !(d.code.is_none() && d.message.starts_with("Exiting due to ")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warning_with_code_and_column() {
+        let output = "%Warning-WIDTH: rtl/top.v:12:18: Operator ASSIGNW expects 4 bits on the \
+                      Assign RHS, but Assign RHS's CONST '5'h1f' generates 5 bits.\n";
+        assert_eq!(
+            parse(output),
+            vec![Diagnostic {
+                code: Some("WIDTH".to_string()),
+                severity: Severity::Warning,
+                file: Some(PathBuf::from("rtl/top.v")),
+                line: Some(12),
+                col: Some(18),
+                message: "Operator ASSIGNW expects 4 bits on the Assign RHS, but Assign RHS's \
+                          CONST '5'h1f' generates 5 bits."
+                    .to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn errors_without_code_or_location() {
+        let output = "%Error: rtl/top.v:3:8: Cannot find file containing module: 'missing'\n\
+                      %Error: Exiting due to 1 error(s)\n";
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].is_error());
+        assert_eq!(diagnostics[0].code, None);
+        assert_eq!(diagnostics[0].file, Some(PathBuf::from("rtl/top.v")));
+        assert_eq!(
+            (diagnostics[0].line, diagnostics[0].col),
+            (Some(3), Some(8))
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Cannot find file containing module: 'missing'"
+        );
+    }
+
+    #[test]
+    fn errors_without_location() {
+        let diagnostics = parse("%Error: Cannot find verilator_bin\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].file, None);
+        assert_eq!(diagnostics[0].line, None);
+        assert_eq!(diagnostics[0].message, "Cannot find verilator_bin");
+    }
+
+    #[test]
+    fn without_column() {
+        let diagnostics = parse("%Warning-UNUSED: rtl/top.v:5: Signal is not used: x\n");
+        assert_eq!(diagnostics[0].line, Some(5));
+        assert_eq!(diagnostics[0].col, None);
+        assert_eq!(diagnostics[0].message, "Signal is not used: x");
+    }
+
+    #[test]
+    fn continuation_lines() {
+        let output = r#"%Warning-UNUSEDSIGNAL: rtl/top.v:5:11: Signal is not used: 'x'
+                                      : ... note: In instance 'top'
+    5 |     wire [3:0] x;
+      |                ^
+                      ... For warning description see https://verilator.org/warn/UNUSEDSIGNAL?v=5.020
+                      ... Use "/* verilator lint_off UNUSEDSIGNAL */" and lint_on around source to disable this message.
+%Error: Exiting due to 1 warning(s)
+"#;
+        let diagnostics = parse(output);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("UNUSEDSIGNAL"));
+        assert_eq!(diagnostics[0].message, "Signal is not used: 'x'");
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "rtl/top.v:5:11: %Warning-UNUSEDSIGNAL: Signal is not used: 'x'"
+        );
+    }
+}
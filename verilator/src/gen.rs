@@ -1,11 +1,30 @@
-use verilator_version;
+mod classes;
+mod depfile;
+mod filelist;
+mod runtime;
+
+pub use self::classes::Classes;
+pub use self::filelist::FileList;
+
+use self::runtime::Runtime;
+
 use cc;
-use std::io::ErrorKind;
+use diagnostic::{self, Diagnostic};
+use install::locate_bin;
+#[cfg(feature = "manifest")]
+use manifest::{Manifest, ManifestError};
+#[cfg(feature = "module")]
+use module::{ModuleError, ModuleGenerator};
+use std::borrow::Cow;
+use std::error::Error;
+use std::ffi::OsString;
+use std::io::{self, ErrorKind};
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::{env, fs};
+use std::process::{Command, Stdio};
+use std::{env, fmt, fs};
+use {has_verilated_context, InstallError, VerilatorInstall, Version};
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Standard {
     Verilog1995,
     Verilog2001,
@@ -15,21 +34,283 @@ pub enum Standard {
     SystemVerilog2012,
 }
 
+/// The value of a top-level parameter override passed with `-G`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Parameter {
+    /// An unsized integer, such as `42`.
+    Int(i64),
+    /// A real number, such as `1.5`.
+    Real(f64),
+    /// A string, passed to verilator quoted.
+    Str(String),
+    /// A sized literal of the given width in bits, such as `8'hff`.
+    Sized(u32, u64),
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Parameter::Int(v) => write!(f, "{}", v),
+            Parameter::Real(v) => write!(f, "{:?}", v),
+            Parameter::Str(ref v) => {
+                f.write_str("\"")?;
+                for c in v.chars() {
+                    if c == '"' || c == '\\' {
+                        f.write_str("\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                f.write_str("\"")
+            }
+            Parameter::Sized(width, v) => write!(f, "{}'h{:x}", width, v),
+        }
+    }
+}
+
+impl Parameter {
+    /// Whether the value of a sized literal fits in its width.
+    fn fits(&self) -> bool {
+        match *self {
+            Parameter::Sized(width, v) => width > 0 && (width >= 64 || v >> width == 0),
+            _ => true,
+        }
+    }
+}
+
+macro_rules! parameter_from_int {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Parameter {
+            fn from(v: $t) -> Parameter {
+                Parameter::Int(i64::from(v))
+            }
+        })*
+    };
+}
+
+parameter_from_int!(i8, i16, i32, i64, u8, u16, u32);
+
+impl From<bool> for Parameter {
+    fn from(v: bool) -> Parameter {
+        Parameter::Sized(1, v as u64)
+    }
+}
+
+impl From<f64> for Parameter {
+    fn from(v: f64) -> Parameter {
+        Parameter::Real(v)
+    }
+}
+
+impl<'a> From<&'a str> for Parameter {
+    fn from(v: &'a str) -> Parameter {
+        Parameter::Str(v.to_string())
+    }
+}
+
+impl From<String> for Parameter {
+    fn from(v: String) -> Parameter {
+        Parameter::Str(v)
+    }
+}
+
+/// Profile guided optimization of the model's C++, passed to the compiler
+/// as `-fprofile-generate` or `-fprofile-use`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pgo {
+    /// Instrument the model to write profiles into a directory when it
+    /// exits.
+    Generate(PathBuf),
+    /// Optimize the model using the profiles in a directory or, for clang,
+    /// a merged `.profdata` file.
+    Use(PathBuf),
+}
+
+/// Errors reported by [`Verilator::try_build`](struct.Verilator.html#method.try_build).
+#[derive(Debug)]
+pub enum VerilatorError {
+    /// The verilator executable could not be run.
+    NotFound(PathBuf),
+    /// The version reported by verilator could not be determined.
+    VersionUnsupported,
+    /// Verilator ran but did not exit successfully.
+    VerilateFailed {
+        stderr: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// Compiling the verilated C++ failed.
+    CompileFailed(cc::Error),
+    /// A required environment variable, such as `OUT_DIR`, is not defined.
+    MissingEnv(String),
+    /// The model's options are incompatible with the runtime compiled by the
+    /// `verilated` crate.
+    RuntimeMismatch(String),
+    /// An option is not supported by the verilator installation or the C++
+    /// compiler.
+    Unsupported(String),
+    /// A parameter override is invalid, such as a sized literal wider than
+    /// its width.
+    InvalidParameter(String),
+    /// The shims of the model could not be generated.
+    #[cfg(feature = "module")]
+    Module(ModuleError),
+    /// The `[package.metadata.verilator]` table of the `Cargo.toml` at
+    /// `path` could not be loaded.
+    #[cfg(feature = "manifest")]
+    Manifest { path: PathBuf, error: ManifestError },
+    /// The verilator installation is unusable or older than the
+    /// [`min_version`](struct.Verilator.html#method.min_version).
+    Install(InstallError),
+    /// Any other I/O error encountered while running verilator.
+    Io(io::Error),
+}
+
+impl fmt::Display for VerilatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerilatorError::NotFound(ref exe) => write!(
+                f,
+                "failed to execute `{}`\nis `verilator` not installed?",
+                exe.display()
+            ),
+            VerilatorError::VersionUnsupported => {
+                write!(f, "failed to determine verilator version")
+            }
+            VerilatorError::VerilateFailed { ref stderr, .. } => {
+                write!(f, "verilator did not execute successfully:\n{}", stderr)
+            }
+            VerilatorError::CompileFailed(ref e) => {
+                write!(f, "failed to compile verilated C++: {}", e)
+            }
+            VerilatorError::MissingEnv(ref v) => {
+                write!(f, "environment variable `{}` not defined", v)
+            }
+            VerilatorError::RuntimeMismatch(ref reason) => write!(
+                f,
+                "model is incompatible with the `verilated` runtime: {}",
+                reason
+            ),
+            VerilatorError::Unsupported(ref reason) => write!(f, "unsupported option: {}", reason),
+            VerilatorError::InvalidParameter(ref reason) => {
+                write!(f, "invalid parameter: {}", reason)
+            }
+            #[cfg(feature = "module")]
+            VerilatorError::Module(ref e) => write!(f, "failed to generate shims: {}", e),
+            #[cfg(feature = "manifest")]
+            VerilatorError::Manifest {
+                ref path,
+                ref error,
+            } => write!(f, "{}: {}", path.display(), error),
+            VerilatorError::Install(ref e) => e.fmt(f),
+            VerilatorError::Io(ref e) => write!(f, "failed to execute command: {}", e),
+        }
+    }
+}
+
+impl Error for VerilatorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            VerilatorError::CompileFailed(ref e) => Some(e),
+            #[cfg(feature = "module")]
+            VerilatorError::Module(ref e) => Some(e),
+            #[cfg(feature = "manifest")]
+            VerilatorError::Manifest { ref error, .. } => Some(error),
+            VerilatorError::Install(ref e) => e.source(),
+            VerilatorError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for VerilatorError {
+    fn from(e: io::Error) -> VerilatorError {
+        VerilatorError::Io(e)
+    }
+}
+
+#[cfg(feature = "module")]
+impl From<ModuleError> for VerilatorError {
+    fn from(e: ModuleError) -> VerilatorError {
+        VerilatorError::Module(e)
+    }
+}
+
+impl From<InstallError> for VerilatorError {
+    fn from(e: InstallError) -> VerilatorError {
+        match e {
+            InstallError::NotFound(bin, _) => VerilatorError::NotFound(bin),
+            InstallError::UnknownVersion(_) => VerilatorError::VersionUnsupported,
+            e => VerilatorError::Install(e),
+        }
+    }
+}
+
 /// Builder style configuration for running verilator.
 pub struct Verilator {
     target: Option<String>,
     host: Option<String>,
     out_dir: Option<PathBuf>,
     root: Option<PathBuf>,
+    min_version: Option<Version>,
+    prefix: Option<String>,
     files: Vec<(PathBuf, Option<Standard>)>,
+    file_lists: Vec<(PathBuf, bool)>,
     module_directories: Vec<PathBuf>,
+    include_directories: Vec<PathBuf>,
+    defines: Vec<(String, Option<String>)>,
+    parameters: Vec<(String, Parameter)>,
     coverage: bool,
     trace: bool,
+    trace_fst: bool,
+    trace_depth: Option<u32>,
+    trace_structs: bool,
+    trace_params: bool,
+    trace_max_array: Option<u32>,
+    trace_max_width: Option<u32>,
     optimized: bool, 
+    threads: Option<u32>,
+    cpp_opt_level: Option<u32>,
+    cpp_debug: Option<bool>,
+    native_cpu: bool,
+    lto: bool,
+    pgo: Option<Pgo>,
     suppress_warnings: Vec<String>,
+    error_warnings: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+    classes: Option<Classes>,
+    dry_run: bool,
+    #[cfg(feature = "module")]
+    rust_modules: Vec<PathBuf>,
+    #[cfg(feature = "module")]
+    checked_ports: bool,
+    #[cfg(feature = "module")]
+    rust_bindings: Option<String>,
+    #[cfg(feature = "module")]
+    clock_port: Option<String>,
+    #[cfg(feature = "module")]
+    reset_port: Option<String>,
 }
 
 impl Verilator {
+    /// Create a builder configured from the `[package.metadata.verilator]`
+    /// table of the `Cargo.toml` at `path`.
+    ///
+    /// The top module is not part of the builder; see
+    /// [`Manifest::top`](../manifest/struct.Manifest.html#method.top).
+    #[cfg(feature = "manifest")]
+    pub fn from_manifest<P>(path: P) -> Result<Verilator, ManifestError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut verilator = Verilator::default();
+        Manifest::load(path)?.configure(&mut verilator);
+        Ok(verilator)
+    }
+
+    /// Configures the output directory, which defaults to `$OUT_DIR`.
+    ///
+    /// Each model is verilated into a subdirectory named after its
+    /// [`prefix`](#method.prefix), so several models can be built into the
+    /// same output directory.
     pub fn out_dir<P>(&mut self, out: P) -> &mut Verilator
     where
         P: AsRef<Path>,
@@ -46,6 +327,54 @@ impl Verilator {
         self
     }
 
+    /// Fail with `VerilatorError::Install` if the verilator found is older
+    /// than `version`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use verilator::Version;
+    ///
+    /// verilator::gen::Verilator::default()
+    ///     .min_version(Version::new(5, 0, 0))
+    ///     .file("rtl/top.sv")
+    ///     .build("top");
+    /// ```
+    pub fn min_version(&mut self, version: Version) -> &mut Verilator {
+        self.min_version = Some(version);
+        self
+    }
+
+    /// Name the generated C++ classes, files and library after `prefix`
+    /// with `--prefix`, rather than the default of `V` followed by the top
+    /// module name.
+    ///
+    /// Structs annotated `#[module(name)]` are bound to the model with the
+    /// prefix `V<name>`, so verilating the same top module twice needs two
+    /// prefixes.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // #[module(dut)] pub struct Dut { ... }
+    /// // #[module(reference)] pub struct Reference { ... }
+    /// Verilator::default()
+    ///     .file("rtl/core.v")
+    ///     .rust_module("src/lib.rs")
+    ///     .prefix("Vdut")
+    ///     .build("core");
+    /// Verilator::default()
+    ///     .file("rtl/core.v")
+    ///     .parameter("FAST_PATH", 0)
+    ///     .rust_module("src/lib.rs")
+    ///     .prefix("Vreference")
+    ///     .build("core");
+    /// ```
+    pub fn prefix(&mut self, prefix: &str) -> &mut Verilator {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
     fn _file(&mut self, p: &Path, s: Option<Standard>) -> &mut Verilator {
         self.files.push((p.to_path_buf(), s));
         self
@@ -76,6 +405,31 @@ impl Verilator {
         self
     }
 
+    /// Add a verilator file list, passed with `-f`.
+    ///
+    /// Relative paths inside the list are relative to the current directory.
+    /// Every file the list references, including nested lists, is reported
+    /// to cargo with `rerun-if-changed`.
+    pub fn file_list<P>(&mut self, p: P) -> &mut Verilator
+    where
+        P: AsRef<Path>,
+    {
+        self.file_lists.push((p.as_ref().to_path_buf(), false));
+        self
+    }
+
+    /// Add a verilator file list, passed with `-F`.
+    ///
+    /// Like [`file_list`](#method.file_list), but relative paths inside the
+    /// list are relative to the directory containing it.
+    pub fn file_list_relative<P>(&mut self, p: P) -> &mut Verilator
+    where
+        P: AsRef<Path>,
+    {
+        self.file_lists.push((p.as_ref().to_path_buf(), true));
+        self
+    }
+
     /// Add a directory to the `-y` or path to search for modules
     ///
     /// # Example
@@ -98,6 +452,141 @@ impl Verilator {
         self
     }
 
+    /// Generate the FFI shims for the `#[module]` structs in the Rust source
+    /// `krate` bound to this model with `ModuleGenerator`, and compile them
+    /// with the model.
+    ///
+    /// The shims are generated into the output directory, with the same
+    /// tracing options as the model, when the model is built. Only the
+    /// structs bound to the model's [`prefix`](#method.prefix) are
+    /// generated, so each shim is compiled exactly once when several models
+    /// share one Rust source.
+    ///
+    /// The `#[port]` fields of those structs are checked against the ports of
+    /// the top module, read with `verilator --xml-only`, so a missing port or
+    /// a direction or width that differs from the Verilog fails the build at
+    /// the field, as with
+    /// [`ModuleGenerator::netlist`](../module/struct.ModuleGenerator.html#method.netlist).
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// verilator::gen::Verilator::default()
+    ///     .file("rtl/top.v")
+    ///     .rust_module("src/main.rs")
+    ///     .build("top");
+    /// ```
+    #[cfg(feature = "module")]
+    pub fn rust_module<P>(&mut self, krate: P) -> &mut Verilator
+    where
+        P: AsRef<Path>,
+    {
+        self.rust_modules.push(krate.as_ref().to_path_buf());
+        self
+    }
+
+    /// Generate `rust_module` setters which panic when a value does not fit
+    /// in the width of the port, as with
+    /// [`ModuleGenerator::with_checked_ports`](../module/struct.ModuleGenerator.html#method.with_checked_ports).
+    #[cfg(feature = "module")]
+    pub fn with_checked_ports(&mut self, t: bool) -> &mut Verilator {
+        self.checked_ports = t;
+        self
+    }
+
+    /// Generate a struct `rs_ty` wrapping the model from the ports of the top
+    /// module, without a `#[module]` struct, and compile its shim with the
+    /// model.
+    ///
+    /// The ports are read from the netlist written by `verilator --xml-only`
+    /// for the same sources, so every port gets an accessor sized and signed
+    /// as declared. The struct is written to `$OUT_DIR/<rs_ty>.rs`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // build.rs
+    /// verilator::gen::Verilator::default()
+    ///     .file("rtl/top.v")
+    ///     .rust_bindings("Top")
+    ///     .clock_port("clk_i")
+    ///     .build("top");
+    ///
+    /// // src/main.rs
+    /// include!(concat!(env!("OUT_DIR"), "/Top.rs"));
+    /// ```
+    #[cfg(feature = "module")]
+    pub fn rust_bindings(&mut self, rs_ty: &str) -> &mut Verilator {
+        self.rust_bindings = Some(rs_ty.to_string());
+        self
+    }
+
+    /// The input toggled by `clock_toggle()` of the
+    /// [`rust_bindings`](#method.rust_bindings).
+    #[cfg(feature = "module")]
+    pub fn clock_port(&mut self, port: &str) -> &mut Verilator {
+        self.clock_port = Some(port.to_string());
+        self
+    }
+
+    /// The input toggled by `reset_toggle()` of the
+    /// [`rust_bindings`](#method.rust_bindings).
+    #[cfg(feature = "module")]
+    pub fn reset_port(&mut self, port: &str) -> &mut Verilator {
+        self.reset_port = Some(port.to_string());
+        self
+    }
+
+    /// Add a directory to the `+incdir+` path searched for `` `include``
+    /// files.
+    pub fn include_dir<P>(&mut self, dir: P) -> &mut Verilator
+    where
+        P: AsRef<Path>,
+    {
+        self.include_directories.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Define a preprocessor macro, as if by `` `define``.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// verilator::gen::Verilator::default()
+    ///     .define("SIMULATION", None)
+    ///     .define("FIFO_DEPTH", "16")
+    ///     .build(...);
+    /// ```
+    pub fn define<'a, V>(&mut self, var: &str, val: V) -> &mut Verilator
+    where
+        V: Into<Option<&'a str>>,
+    {
+        self.defines
+            .push((var.to_string(), val.into().map(|s| s.to_string())));
+        self
+    }
+
+    /// Override a parameter of the top-level module.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use verilator::gen::{Parameter, Verilator};
+    ///
+    /// Verilator::default()
+    ///     .parameter("WIDTH", 32)
+    ///     .parameter("NAME", "core0")
+    ///     .parameter("MASK", Parameter::Sized(8, 0xf0))
+    ///     .build(...);
+    /// ```
+    pub fn parameter<V>(&mut self, name: &str, val: V) -> &mut Verilator
+    where
+        V: Into<Parameter>,
+    {
+        self.parameters.push((name.to_string(), val.into()));
+        self
+    }
+
     pub fn with_coverage(&mut self, t: bool) -> &mut Verilator {
         self.coverage = t;
         self
@@ -108,11 +597,128 @@ impl Verilator {
         self
     }
 
+    /// Generate FST rather than, or with Verilator 5 as well as, VCD tracing
+    /// code with `--trace-fst`.
+    ///
+    /// Requires the `fst` feature of the `verilated` crate.
+    pub fn with_trace_fst(&mut self, t: bool) -> &mut Verilator {
+        self.trace_fst = t;
+        self
+    }
+
+    /// Only trace signals up to `depth` levels below the top module, with
+    /// `--trace-depth`.
+    pub fn trace_depth(&mut self, depth: u32) -> &mut Verilator {
+        self.trace_depth = Some(depth);
+        self
+    }
+
+    /// Trace packed and unpacked structs as structures rather than flattened
+    /// signals, with `--trace-structs`.
+    pub fn trace_structs(&mut self, t: bool) -> &mut Verilator {
+        self.trace_structs = t;
+        self
+    }
+
+    /// Trace parameters as well as signals. Enabled by default; disabling
+    /// passes `--no-trace-params`.
+    pub fn trace_params(&mut self, t: bool) -> &mut Verilator {
+        self.trace_params = t;
+        self
+    }
+
+    /// Only trace the first `depth` elements of arrays, with
+    /// `--trace-max-array`.
+    pub fn trace_max_array(&mut self, depth: u32) -> &mut Verilator {
+        self.trace_max_array = Some(depth);
+        self
+    }
+
+    /// Don't trace signals wider than `width` bits, with
+    /// `--trace-max-width`.
+    pub fn trace_max_width(&mut self, width: u32) -> &mut Verilator {
+        self.trace_max_width = Some(width);
+        self
+    }
+
+    /// Have verilator optimize the model with `-O3`. The C++ compiler's
+    /// optimization is set separately, with
+    /// [`cpp_opt_level`](#method.cpp_opt_level).
     pub fn with_performance_optimizations(&mut self, t: bool) -> &mut Verilator {
         self.optimized = t;
         self
     }
 
+    /// Generate a multithreaded model with `--threads`, evaluated by `n`
+    /// threads unless changed with `Context::set_threads`.
+    ///
+    /// Requires Verilator 5, as older versions fix the number of threads
+    /// when verilating.
+    pub fn with_threads(&mut self, n: u32) -> &mut Verilator {
+        self.threads = Some(n);
+        self
+    }
+
+    /// Compile the model's C++ at optimization level `level`, rather than
+    /// the level of the Cargo profile.
+    ///
+    /// Simulations are many times slower at `-O0`, the level of debug
+    /// profiles, so even debug builds usually want at least 2.
+    pub fn cpp_opt_level(&mut self, level: u32) -> &mut Verilator {
+        self.cpp_opt_level = Some(level);
+        self
+    }
+
+    /// Compile the model's C++ with or without debug info, rather than as
+    /// the Cargo profile does.
+    pub fn with_cpp_debug(&mut self, t: bool) -> &mut Verilator {
+        self.cpp_debug = Some(t);
+        self
+    }
+
+    /// Compile the model's C++ for the host CPU with `-march=native`. The
+    /// library will not run on older CPUs.
+    pub fn with_native_cpu(&mut self, t: bool) -> &mut Verilator {
+        self.native_cpu = t;
+        self
+    }
+
+    /// Compile the model's C++ with link time optimization.
+    ///
+    /// With GCC, the objects are built with `-ffat-lto-objects`, so they
+    /// still link when the final link is not LTO-aware. With clang, they are
+    /// LLVM bitcode, which only a link with `-Clinker-plugin-lto` in the
+    /// `RUSTFLAGS` can consume, so the build fails without it. Other
+    /// compilers are not supported.
+    pub fn with_lto(&mut self, t: bool) -> &mut Verilator {
+        self.lto = t;
+        self
+    }
+
+    /// Instrument or optimize the model's C++ with profile data.
+    ///
+    /// With GCC, instrumented models link `libgcov`; with clang, the
+    /// profile runtime must be linked by the final link.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use verilator::gen::{Pgo, Verilator};
+    ///
+    /// let profile = std::env::var_os("MODEL_PROFILE");
+    /// let mut verilator = Verilator::default();
+    /// verilator.file("rtl/top.v").cpp_opt_level(3);
+    /// match profile {
+    ///     Some(dir) => verilator.pgo(Pgo::Use(dir.into())),
+    ///     None => verilator.pgo(Pgo::Generate("/tmp/top-profile".into())),
+    /// };
+    /// verilator.build("top");
+    /// ```
+    pub fn pgo(&mut self, pgo: Pgo) -> &mut Verilator {
+        self.pgo = Some(pgo);
+        self
+    }
+
     pub fn warn_width(&mut self, t: bool) -> &mut Verilator {
         if !t {
             self.suppress_warnings.push("width".to_string());
@@ -125,95 +731,209 @@ impl Verilator {
         self
     }
 
-    pub fn build(&mut self, top_module: &str) -> PathBuf {
-        let dst = self
-            .out_dir
-            .clone()
-            .unwrap_or_else(|| PathBuf::from(getenv_unwrap("OUT_DIR")));
+    /// Promote a warning code, such as `WIDTH`, to an error.
+    ///
+    /// Verilator then fails with an `%Error-<code>` diagnostic instead of
+    /// reporting a warning.
+    pub fn warn_as_error(&mut self, warning: &str) -> &mut Verilator {
+        self.error_warnings.push(warning.to_ascii_uppercase());
+        self
+    }
 
-        // Determine ${VERILATOR_ROOT}
-        let verilator_exe = self.find_verilator_exe();
-        let mut cmd = Command::new(verilator_exe.clone());
-        cmd.arg("--getenv").arg("VERILATOR_ROOT");
+    /// Warnings reported by verilator during the last successful build.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
 
-        // Determine the Verilator version
-        let (verilator_major, verilator_minor) = verilator_version().unwrap();
+    /// Lint the sources with `--lint-only -Wall` without generating or
+    /// compiling any C++, returning every warning reported.
+    ///
+    /// The same files, file lists, directories, defines and parameters as
+    /// [`build`](#method.build) are used. Warnings suppressed with `no_warn`
+    /// are not reported, and those promoted with `warn_as_error` fail with
+    /// `VerilatorError::VerilateFailed`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let warnings = verilator::gen::Verilator::default()
+    ///     .file("rtl/top.v")
+    ///     .lint("top")
+    ///     .unwrap();
+    /// assert!(warnings.is_empty());
+    /// ```
+    pub fn lint(&self, top_module: &str) -> Result<Vec<Diagnostic>, VerilatorError> {
+        let mut args = Vec::new();
+        self.source_args(&mut args)?;
+        let install = self.install()?;
+        let mut cmd = install.command();
+        cmd.arg("--lint-only")
+            .arg("-Wall")
+            .arg("-Wno-fatal")
+            .arg("--top-module")
+            .arg(top_module)
+            .args(args);
+        run(&mut cmd, &install.bin)
+    }
 
-        println!("running: {:?}", cmd);
-        let root = match cmd.output() {
-            Ok(output) => PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()),
-            Err(..) => self.verilator_root().unwrap_or_else(|| {
-                // Set root to /verilator/path/to/exe/../
-                let mut root = verilator_exe.clone();
-                root.pop();
-                root.pop();
-                root
-            }),
-        };
-        println!("verilator root: {:?}", root);
+    /// Plan a build without running verilator or compiling anything.
+    ///
+    /// Instead, [`try_build`](#method.try_build) writes the arguments
+    /// verilator would be run with to `<prefix>.args` in the output
+    /// directory, and the C++ files that would be compiled besides
+    /// verilator's own output, including the shims it would generate, to
+    /// `<prefix>.sources`. Verilator need not be installed, so neither the
+    /// [`min_version`](#method.min_version) nor the version needed by
+    /// [`with_threads`](#method.with_threads) is checked.
+    ///
+    /// `<prefix>.args` holds one argument per line, quoted where needed to
+    /// be read back with `verilator -f`. `<prefix>.sources` holds one path
+    /// per line.
+    pub fn dry_run(&mut self, t: bool) -> &mut Verilator {
+        self.dry_run = t;
+        self
+    }
 
-        // Generate .CPP from .V using verilator
-        let mut cmd = Command::new(verilator_exe.clone());
-        cmd.arg("--cc")
-            .arg("-Mdir")
-            .arg(&dst)
-            .arg("--top-module")
-            .arg(top_module);
+    /// The arguments [`try_build`](#method.try_build) runs verilator with
+    /// to verilate `top_module`.
+    ///
+    /// They do not depend on the verilator installation, which need not
+    /// exist.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let args = verilator::gen::Verilator::default()
+    ///     .file("rtl/top.v")
+    ///     .with_trace(true)
+    ///     .args("top")
+    ///     .unwrap();
+    /// assert!(args.iter().any(|arg| arg == "--trace"));
+    /// ```
+    pub fn args(&self, top_module: &str) -> Result<Vec<OsString>, VerilatorError> {
+        let (out_dir, prefix) = self.model_dir(top_module)?;
+        let (args, _) = self.verilate_args(top_module, &out_dir.join(&prefix), &prefix)?;
+        Ok(args)
+    }
 
-        if self.coverage {
-            cmd.arg("--coverage");
+    /// The command [`try_build`](#method.try_build) runs to verilate
+    /// `top_module`.
+    ///
+    /// The `verilator_bin` of the configured root, with `${VERILATOR_ROOT}`
+    /// set, or else the one on `PATH` is run. Unlike a build, this does not
+    /// run it to check the installation.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let cmd = verilator::gen::Verilator::default()
+    ///     .file("rtl/top.v")
+    ///     .with_trace(true)
+    ///     .command("top")
+    ///     .unwrap();
+    /// assert!(cmd.get_args().any(|arg| arg == "--trace"));
+    /// ```
+    pub fn command(&self, top_module: &str) -> Result<Command, VerilatorError> {
+        let root = self.verilator_root();
+        let mut cmd = Command::new(locate_bin(root.as_deref()));
+        if let Some(root) = root {
+            cmd.env("VERILATOR_ROOT", root);
         }
+        cmd.args(self.args(top_module)?);
+        Ok(cmd)
+    }
 
-        if self.trace {
-            cmd.arg("--trace");
-        }
+    /// The generated sources and runtime requirements of the model from the
+    /// last successful build.
+    pub fn classes(&self) -> Option<&Classes> {
+        self.classes.as_ref()
+    }
 
-        if self.optimized {
-            cmd.arg("-O3");
+    /// Run verilator and compile the generated C++ into a static library.
+    ///
+    /// Panics if any step fails. See [`try_build`](#method.try_build) for a
+    /// non-panicking version.
+    pub fn build(&mut self, top_module: &str) -> PathBuf {
+        match self.try_build(top_module) {
+            Ok(dst) => dst,
+            Err(e) => fail(&e.to_string()),
         }
+    }
 
-        for warn in &self.suppress_warnings {
-            cmd.arg(format!("-Wno-{}", warn));
+    /// Run verilator and compile the generated C++ into a static library,
+    /// returning the directory the model was verilated into.
+    ///
+    /// The library is named `<prefix>__ALL`. The verilator runtime is not
+    /// part of it; that is linked once by the `verilated` crate.
+    pub fn try_build(&mut self, top_module: &str) -> Result<PathBuf, VerilatorError> {
+        let (out_dir, prefix) = self.model_dir(top_module)?;
+        let dst = out_dir.join(&prefix);
+
+        if self.dry_run {
+            let (args, lists) = self.verilate_args(top_module, &dst, &prefix)?;
+            return self
+                .plan(&args, top_module, &out_dir, &prefix, &lists)
+                .map(|_| dst);
         }
 
-        for dir in &self.module_directories {
-            cmd.arg("-y");
-            cmd.arg(dir);
+        // Determine ${VERILATOR_ROOT} and the Verilator version
+        let install = self.install()?;
+        let version = (install.version.major, install.version.minor);
+        if self.threads.is_some() && version.0 < 5 {
+            return Err(VerilatorError::Unsupported(format!(
+                "`with_threads` needs verilator 5 to set the threads at runtime, but found {}",
+                install.version
+            )));
         }
 
-        for &(ref file, ref standard) in &self.files {
-            if let Some(standard) = *standard {
-                if let Some(ext) = file.extension() {
-                    let flag = match standard {
-                        Standard::Verilog1995 => &"+1364-1995ext",
-                        Standard::Verilog2001 => &"+1364-2001ext",
-                        Standard::Verilog2005 => &"+1364-2005ext",
-                        Standard::SystemVerilog2005 => &"+1800-2005ext",
-                        Standard::SystemVerilog2009 => &"+1800-2009ext",
-                        Standard::SystemVerilog2012 => &"+1800-2012ext",
-                    };
-                    let flag = format!("{}+{}", flag, ext.to_string_lossy());
-                    cmd.arg(flag);
-                }
-            }
+        // The runtime compiled by the `verilated` crate, when the crate being
+        // built depends on it.
+        let runtime = Runtime::from_env();
 
-            cmd.arg(file);
+        let root = &install.root;
+        println!("verilator {} root: {:?}", install.version, root);
+
+        // Generate .CPP from .V using verilator
+        let (args, lists) = self.verilate_args(top_module, &dst, &prefix)?;
+        let mut cmd = install.command();
+        cmd.args(args);
+        fs::create_dir_all(&dst)?;
+        self.diagnostics = run(&mut cmd, &install.bin)?;
+
+        // Cargo scans the directories for changes to the files verilator may
+        // find in them.
+        for list in &lists {
+            let files = list.lists.iter().chain(&list.sources);
+            let dirs = list
+                .module_directories
+                .iter()
+                .chain(&list.include_directories);
+            for path in files.chain(dirs) {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
         }
 
-        run(&mut cmd, "verilator");
+        // Track every Verilog source and include verilator actually read.
+        let deps = dst.join(format!("{}__ver.d", prefix));
+        let deps = match fs::read_to_string(&deps) {
+            Ok(content) => depfile::parse(&content),
+            Err(..) => self.files.iter().map(|(f, _)| f.clone()).collect(),
+        };
+        // The list includes verilator itself, which may be found through a
+        // symlink or a relative `PATH`.
+        let bin = install.bin.canonicalize().ok();
+        for dep in deps {
+            if bin.is_none() || dep.canonicalize().ok() != bin {
+                println!("cargo:rerun-if-changed={}", dep.display());
+            }
+        }
 
         // Compile the .CPP into library.
-        let target = match self.target.clone() {
-            Some(t) => t,
-            None => {
-                let mut t = getenv_unwrap("TARGET");
-                if t.ends_with("-darwin") {
-                    t += "11";
-                }
-                t
-            }
+        let target = self.target()?;
+        let host = match self.host.clone() {
+            Some(h) => h,
+            None => getenv("HOST")?,
         };
-        let host = self.host.clone().unwrap_or_else(|| getenv_unwrap("HOST"));
 
         let mut cpp_cfg = cc::Build::new();
         cpp_cfg
@@ -248,59 +968,497 @@ impl Verilator {
                 .flag("-Wno-unused-variable")
                 .flag("-Wno-shadow");
         }
+
+        // Code generation of the model, independent of the Cargo profile.
+        if let Some(level) = self.cpp_opt_level {
+            cpp_cfg.opt_level(level);
+        }
+        if let Some(debug) = self.cpp_debug {
+            cpp_cfg.debug(debug);
+        }
+        if self.native_cpu {
+            cpp_cfg.flag_if_supported("-march=native");
+        }
+        if self.lto {
+            if tool.is_like_gnu() {
+                cpp_cfg.flag("-flto").flag("-ffat-lto-objects");
+            } else if tool.is_like_clang()
+                && linker_plugin_lto(&env::var("CARGO_ENCODED_RUSTFLAGS").unwrap_or_default())
+            {
+                cpp_cfg.flag("-flto=thin");
+            } else if tool.is_like_clang() {
+                return Err(VerilatorError::Unsupported(
+                    "`with_lto` needs `-Clinker-plugin-lto` in the RUSTFLAGS to link clang's \
+                     LLVM bitcode"
+                        .to_string(),
+                ));
+            } else {
+                return Err(VerilatorError::Unsupported(format!(
+                    "`with_lto` needs GCC or clang, but the compiler is {}",
+                    tool.path().display()
+                )));
+            }
+        }
+        match self.pgo {
+            Some(Pgo::Generate(ref dir)) => {
+                cpp_cfg.flag(format!("-fprofile-generate={}", dir.display()));
+                if tool.is_like_gnu() {
+                    println!("cargo:rustc-link-lib=gcov");
+                }
+            }
+            Some(Pgo::Use(ref path)) => {
+                println!("cargo:rerun-if-changed={}", path.display());
+                cpp_cfg.flag(format!("-fprofile-use={}", path.display()));
+                // Not every generated file is reached by a simulation.
+                cpp_cfg.flag_if_supported("-Wno-missing-profile");
+            }
+            None => {}
+        }
+
         cpp_cfg
             .include(root.join("include"))
             .include(root.join("include/vltstd"))
-            .include(&dst)
-            .file(dst.join(format!("V{}.cpp", top_module)))
-            .file(dst.join(format!("V{}__Syms.cpp", top_module)));
+            .include(&dst);
 
-        if verilator_major > 4 || verilator_minor >= 38 {
-            cpp_cfg.file(dst.join(format!("V{}__Slow.cpp", top_module)));
+        // Compile exactly the files verilator says it generated.
+        let classes = fs::read_to_string(dst.join(format!("{}_classes.mk", prefix)))?;
+        let classes = Classes::parse(&classes);
+        if let Some(ref runtime) = runtime {
+            self.check_runtime(runtime, install.version, &classes)?;
+        }
+        for file in classes.generated_sources() {
+            cpp_cfg.file(dst.join(file));
+        }
+        // The model and its shims are compiled with the features it was
+        // verilated with, which `check_runtime` found the runtime supports.
+        for (var, val) in classes.defines() {
+            cpp_cfg.define(var, val);
         }
+        match runtime {
+            // Must match the runtime compiled by the `verilated` crate.
+            Some(ref runtime) => {
+                for (var, val) in &runtime.defines {
+                    if classes.get(var).is_empty() {
+                        cpp_cfg.define(var, val.as_deref());
+                    }
+                }
+            }
+            None => {
+                if has_verilated_context(version) {
+                    cpp_cfg.define("VL_TIME_CONTEXT", None);
+                }
+            }
+        }
+
+        // The ports of the top module, to check `rust_module` structs against
+        // and to generate `rust_bindings` from.
+        #[cfg(feature = "module")]
+        let netlist = if self.rust_bindings.is_some() || !self.rust_modules.is_empty() {
+            Some(self.netlist(&install, top_module, &dst, &prefix)?)
+        } else {
+            None
+        };
+
+        #[cfg(feature = "module")]
+        for krate in &self.rust_modules {
+            let mut generator = self.module_generator(&out_dir, &prefix);
+            generator.with_context(install.capabilities.context);
+            if let Some(ref xml) = netlist {
+                generator.netlist(xml, top_module);
+            }
+            for f in generator.try_generate(krate)? {
+                cpp_cfg.file(f);
+            }
+        }
+
+        for f in self.cpp_files(&lists) {
+            println!("cargo:rerun-if-changed={}", f.display());
+            cpp_cfg.file(f);
+        }
+
+        #[cfg(feature = "module")]
+        if let (Some(rs_ty), Some(xml)) = (&self.rust_bindings, netlist) {
+            let mut generator = self.module_generator(&out_dir, &prefix);
+            generator.with_context(install.capabilities.context);
+            if let Some(ref clock) = self.clock_port {
+                generator.clock(clock);
+            }
+            if let Some(ref reset) = self.reset_port {
+                generator.reset(reset);
+            }
+            cpp_cfg.file(generator.generate_bindings(xml, top_module, rs_ty)?);
+        }
+
+        cpp_cfg
+            .try_compile(&format!("{}__ALL", prefix))
+            .map_err(VerilatorError::CompileFailed)?;
+        self.classes = Some(classes);
+
+        Ok(dst)
+    }
+
+    /// The output directory and prefix of the model for `top_module`.
+    fn model_dir(&self, top_module: &str) -> Result<(PathBuf, String), VerilatorError> {
+        let out_dir = match self.out_dir.clone() {
+            Some(out_dir) => out_dir,
+            None => PathBuf::from(getenv("OUT_DIR")?),
+        };
+        let prefix = self
+            .prefix
+            .clone()
+            .unwrap_or_else(|| format!("V{}", top_module));
+        Ok((out_dir, prefix))
+    }
 
-        for &(ref f, _) in &self.files {
-            match f.extension() {
-                Some(ext) if ext == "c" || ext == "cpp" => {
-                    cpp_cfg.file(f);
+    fn target(&self) -> Result<String, VerilatorError> {
+        match self.target.clone() {
+            Some(t) => Ok(t),
+            None => {
+                let mut t = getenv("TARGET")?;
+                if t.ends_with("-darwin") {
+                    t += "11";
                 }
-                _ => {}
-            };
+                Ok(t)
+            }
         }
+    }
+
+    /// The arguments verilating `top_module` into `dst`, with the file
+    /// lists among them.
+    fn verilate_args(
+        &self,
+        top_module: &str,
+        dst: &Path,
+        prefix: &str,
+    ) -> Result<(Vec<OsString>, Vec<FileList>), VerilatorError> {
+        let mut args: Vec<OsString> = vec![
+            "--cc".into(),
+            "--MMD".into(),
+            "-Mdir".into(),
+            dst.into(),
+            "--top-module".into(),
+            top_module.into(),
+            "--prefix".into(),
+            prefix.into(),
+        ];
 
         if self.coverage {
-            cpp_cfg.define("VM_COVERAGE", "1");
+            args.push("--coverage".into());
         }
 
         if self.trace {
-            cpp_cfg
-                .define("VM_TRACE", "1")
-                .file(dst.join(format!("V{}__Trace.cpp", top_module)))
-                .file(dst.join(format!("V{}__Trace__Slow.cpp", top_module)));
+            args.push("--trace".into());
+        }
+
+        if self.trace_fst {
+            args.push("--trace-fst".into());
         }
 
-        cpp_cfg.compile(&format!("V{}__ALL", top_module));
+        if let Some(depth) = self.trace_depth {
+            args.push("--trace-depth".into());
+            args.push(depth.to_string().into());
+        }
+
+        if self.trace_structs {
+            args.push("--trace-structs".into());
+        }
 
-        dst
+        if !self.trace_params {
+            args.push("--no-trace-params".into());
+        }
+
+        if let Some(depth) = self.trace_max_array {
+            args.push("--trace-max-array".into());
+            args.push(depth.to_string().into());
+        }
+
+        if let Some(width) = self.trace_max_width {
+            args.push("--trace-max-width".into());
+            args.push(width.to_string().into());
+        }
+
+        if self.optimized {
+            args.push("-O3".into());
+        }
+
+        if let Some(threads) = self.threads {
+            args.push("--threads".into());
+            args.push(threads.to_string().into());
+        }
+
+        let lists = self.source_args(&mut args)?;
+        Ok((args, lists))
     }
 
-    fn find_verilator_exe(&self) -> PathBuf {
-        // Check ${VERILATOR_ROOT} first...
-        if let Some(mut root) = self.verilator_root() {
-            root.push("bin/verilator_bin");
-            if root.is_file() {
-                return root;
+    /// The C and C++ files among the sources, compiled besides verilator's
+    /// output.
+    fn cpp_files(&self, lists: &[FileList]) -> Vec<PathBuf> {
+        let listed = lists.iter().flat_map(|list| &list.sources);
+        self.files
+            .iter()
+            .map(|(f, _)| f)
+            .chain(listed)
+            .filter(|f| is_cpp(f))
+            .cloned()
+            .collect()
+    }
+
+    /// A shim generator for the model verilated with `--prefix prefix`.
+    #[cfg(feature = "module")]
+    fn module_generator(&self, out_dir: &Path, prefix: &str) -> ModuleGenerator {
+        let mut generator = ModuleGenerator::default();
+        generator
+            .out_dir(out_dir)
+            .prefix(prefix)
+            .with_trace_fst(self.trace_fst)
+            .with_checked_ports(self.checked_ports);
+        generator
+    }
+
+    /// Write the netlist of `top_module` to `<dst>/<prefix>.xml` with
+    /// `verilator --xml-only`, for the [`rust_bindings`](#method.rust_bindings)
+    /// and checking the ports of `rust_module` structs.
+    ///
+    /// This is a pass of its own, through verilator's front end only, as
+    /// `--xml-output` implies `--xml-only` and so cannot be added to the
+    /// run generating the model. The build has reported any warnings
+    /// already, so they are not repeated.
+    #[cfg(feature = "module")]
+    fn netlist(
+        &self,
+        install: &VerilatorInstall,
+        top_module: &str,
+        dst: &Path,
+        prefix: &str,
+    ) -> Result<PathBuf, VerilatorError> {
+        let xml = dst.join(format!("{}.xml", prefix));
+        let mut args = Vec::new();
+        self.source_args(&mut args)?;
+        let mut cmd = install.command();
+        cmd.arg("--xml-only")
+            .arg("--xml-output")
+            .arg(&xml)
+            .arg("-Mdir")
+            .arg(dst)
+            .arg("-Wno-fatal")
+            .arg("--top-module")
+            .arg(top_module)
+            .args(args);
+        println!("running: {:?}", cmd);
+        let output = cmd.output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            eprint!("{}", stderr);
+            let diagnostics = diagnostic::parse(&stderr);
+            return Err(VerilatorError::VerilateFailed {
+                stderr,
+                diagnostics,
+            });
+        }
+        Ok(xml)
+    }
+
+    /// Write the plan for a [`dry_run`](#method.dry_run) into `out_dir`.
+    #[cfg_attr(not(feature = "module"), allow(unused_variables))]
+    fn plan(
+        &self,
+        args: &[OsString],
+        top_module: &str,
+        out_dir: &Path,
+        prefix: &str,
+        lists: &[FileList],
+    ) -> Result<(), VerilatorError> {
+        fs::create_dir_all(out_dir)?;
+        let mut args_file = String::new();
+        for arg in args {
+            args_file.push_str(&quote_arg(&arg.to_string_lossy()));
+            args_file.push('\n');
+        }
+        fs::write(out_dir.join(format!("{}.args", prefix)), args_file)?;
+
+        let mut files = Vec::new();
+        #[cfg(feature = "module")]
+        {
+            let generator = self.module_generator(out_dir, prefix);
+            for krate in &self.rust_modules {
+                files.extend(generator.shim_files(krate)?);
+            }
+            if self.rust_bindings.is_some() {
+                files.push(generator.bindings_file(top_module)?);
+            }
+        }
+        files.extend(self.cpp_files(lists));
+
+        let mut sources = String::new();
+        for f in files {
+            sources.push_str(&f.to_string_lossy());
+            sources.push('\n');
+        }
+        fs::write(out_dir.join(format!("{}.sources", prefix)), sources)?;
+        Ok(())
+    }
+
+    /// Add the warning options and every source, directory, define and
+    /// parameter to `args`, returning the file lists among them.
+    fn source_args(&self, args: &mut Vec<OsString>) -> Result<Vec<FileList>, VerilatorError> {
+        for warn in &self.suppress_warnings {
+            args.push(format!("-Wno-{}", warn).into());
+        }
+
+        for warn in &self.error_warnings {
+            args.push(format!("-Werror-{}", warn).into());
+        }
+
+        for dir in &self.module_directories {
+            args.push("-y".into());
+            args.push(dir.into());
+        }
+
+        for dir in &self.include_directories {
+            let mut flag = OsString::from("+incdir+");
+            flag.push(dir);
+            args.push(flag);
+        }
+
+        for (var, val) in &self.defines {
+            match *val {
+                Some(ref val) => args.push(format!("+define+{}={}", var, val).into()),
+                None => args.push(format!("+define+{}", var).into()),
+            };
+        }
+
+        for (name, val) in &self.parameters {
+            if let Parameter::Sized(width, v) = *val {
+                if !val.fits() {
+                    return Err(VerilatorError::InvalidParameter(format!(
+                        "value {} of `{}` does not fit in {} bits",
+                        v, name, width
+                    )));
+                }
             }
+            args.push(format!("-G{}={}", name, val).into());
+        }
+
+        let mut lists = Vec::new();
+        for &(ref path, relative) in &self.file_lists {
+            lists.push(FileList::parse(path, relative)?);
+            args.push(if relative { "-F" } else { "-f" }.into());
+            args.push(path.into());
         }
 
-        // Otherwise, check PATH
-        find_in_path("verilator_bin".as_ref())
+        for &(ref file, ref standard) in &self.files {
+            if let Some(standard) = *standard {
+                if let Some(ext) = file.extension() {
+                    let flag = match standard {
+                        Standard::Verilog1995 => &"+1364-1995ext",
+                        Standard::Verilog2001 => &"+1364-2001ext",
+                        Standard::Verilog2005 => &"+1364-2005ext",
+                        Standard::SystemVerilog2005 => &"+1800-2005ext",
+                        Standard::SystemVerilog2009 => &"+1800-2009ext",
+                        Standard::SystemVerilog2012 => &"+1800-2012ext",
+                    };
+                    let flag = format!("{}+{}", flag, ext.to_string_lossy());
+                    args.push(flag.into());
+                }
+            }
+
+            args.push(file.into());
+        }
+
+        Ok(lists)
+    }
+
+    /// The installation at the configured root or, failing that, the one
+    /// [`VerilatorInstall::discover`](../struct.VerilatorInstall.html#method.discover)
+    /// finds.
+    fn install(&self) -> Result<VerilatorInstall, VerilatorError> {
+        let install = match self.verilator_root() {
+            Some(root) => VerilatorInstall::from_root(root)?,
+            None => VerilatorInstall::discover()?,
+        };
+        match self.min_version {
+            Some(min) => Ok(install.require(min)?),
+            None => Ok(install),
+        }
     }
 
     fn verilator_root(&self) -> Option<PathBuf> {
         self.root
             .clone()
             .or_else(|| env::var_os("VERILATOR_ROOT").map(PathBuf::from))
+            .or_else(|| Runtime::from_env().and_then(|runtime| runtime.root))
+    }
+
+    /// Check the options the model is verilated with can be linked against
+    /// `runtime`.
+    fn check_runtime(
+        &self,
+        runtime: &Runtime,
+        version: Version,
+        classes: &Classes,
+    ) -> Result<(), VerilatorError> {
+        let (major, minor) = (runtime.version.major, runtime.version.minor);
+        if (major, minor) != (version.major, version.minor) {
+            return Err(VerilatorError::RuntimeMismatch(format!(
+                "runtime was compiled from verilator {}, but the model is verilated by {}",
+                runtime.version, version
+            )));
+        }
+        if self.coverage && !runtime.coverage {
+            return Err(VerilatorError::RuntimeMismatch(
+                "model uses `--coverage`, but the runtime was compiled without the `coverage` feature"
+                    .to_string(),
+            ));
+        }
+        // Before Verilator 5, `--trace-fst` replaces VCD tracing.
+        let trace_vcd = self.trace && !(self.trace_fst && version.major < 5);
+        if trace_vcd && !runtime.vcd {
+            return Err(VerilatorError::RuntimeMismatch(
+                "model uses `--trace`, but the runtime was compiled without the `vcd` feature"
+                    .to_string(),
+            ));
+        }
+        if self.trace_fst && !runtime.fst {
+            return Err(VerilatorError::RuntimeMismatch(
+                "model uses `--trace-fst`, but the runtime was compiled without the `fst` feature"
+                    .to_string(),
+            ));
+        }
+        // The parts of the runtime verilator's makefiles would link the
+        // model against.
+        for file in classes.runtime_sources() {
+            let (feature, compiled) = match file.as_str() {
+                "verilated_cov.cpp" => ("coverage", runtime.coverage),
+                "verilated_dpi.cpp" => ("dpi", runtime.dpi),
+                "verilated_save.cpp" => ("save", runtime.save),
+                "verilated_vcd_c.cpp" => ("vcd", runtime.vcd),
+                "verilated_vpi.cpp" => ("vpi", runtime.vpi),
+                "verilated_fst_c.cpp" => ("fst", runtime.fst),
+                "verilated_threads.cpp" => ("threads", runtime.threads),
+                _ => continue,
+            };
+            if !compiled {
+                return Err(VerilatorError::RuntimeMismatch(format!(
+                    "model needs `{}`, but the runtime was compiled without the `{}` feature",
+                    file, feature
+                )));
+            }
+        }
+        // The runtime must support every feature the model was verilated
+        // with. A model without a feature can use a runtime with it, and
+        // keeps its own definition, so its shims leave the feature out.
+        for (var, val) in classes.defines() {
+            match runtime.define(var) {
+                Some(runtime_val) if val != "0" && runtime_val != val => {
+                    return Err(VerilatorError::RuntimeMismatch(format!(
+                        "model needs `{}={}`, but the runtime was compiled with `{}={}`",
+                        var, val, var, runtime_val
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
     }
 }
 
@@ -311,50 +1469,328 @@ impl Default for Verilator {
             host: None,
             out_dir: None,
             root: None,
+            min_version: None,
+            prefix: None,
             files: Vec::new(),
+            file_lists: Vec::new(),
             module_directories: Vec::new(),
+            include_directories: Vec::new(),
+            defines: Vec::new(),
+            parameters: Vec::new(),
             coverage: false,
             trace: false,
+            trace_fst: false,
+            trace_depth: None,
+            trace_structs: false,
+            trace_params: true,
+            trace_max_array: None,
+            trace_max_width: None,
             optimized: false,
+            threads: None,
+            cpp_opt_level: None,
+            cpp_debug: None,
+            native_cpu: false,
+            lto: false,
+            pgo: None,
             suppress_warnings: Vec::new(),
+            error_warnings: Vec::new(),
+            diagnostics: Vec::new(),
+            classes: None,
+            dry_run: false,
+            #[cfg(feature = "module")]
+            rust_modules: Vec::new(),
+            #[cfg(feature = "module")]
+            checked_ports: false,
+            #[cfg(feature = "module")]
+            rust_bindings: None,
+            #[cfg(feature = "module")]
+            clock_port: None,
+            #[cfg(feature = "module")]
+            reset_port: None,
         }
     }
 }
 
-fn find_in_path(path: &Path) -> PathBuf {
-    env::split_paths(&env::var_os("PATH").unwrap_or_default())
-        .map(|p| p.join(path))
-        .find(|p| fs::metadata(p).is_ok())
-        .unwrap_or_else(|| path.to_owned())
-}
-
-fn run(cmd: &mut Command, program: &str) {
+/// Run `cmd`, echoing its stderr and returning verilator's diagnostics.
+///
+/// Under a build script, where `OUT_DIR` is set, each diagnostic is also
+/// reported as a cargo warning.
+fn run(cmd: &mut Command, program: &Path) -> Result<Vec<Diagnostic>, VerilatorError> {
     println!("running: {:?}", cmd);
-    let status = match cmd.status() {
-        Ok(status) => status,
+    let output = match cmd.stdout(Stdio::inherit()).stderr(Stdio::piped()).output() {
+        Ok(output) => output,
         Err(ref e) if e.kind() == ErrorKind::NotFound => {
-            fail(&format!(
-                "failed to execute command: {}\nis `{}` not installed?",
-                e, program
-            ));
+            return Err(VerilatorError::NotFound(program.to_path_buf()));
         }
-        Err(e) => fail(&format!("failed to execute command: {}", e)),
+        Err(e) => return Err(e.into()),
     };
-    if !status.success() {
-        fail(&format!(
-            "command did not execute successfully, got: {}",
-            status
-        ));
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    eprint!("{}", stderr);
+    let diagnostics = diagnostic::parse(&stderr);
+    if env::var_os("OUT_DIR").is_some() {
+        for diagnostic in &diagnostics {
+            println!("cargo:warning={}", diagnostic);
+        }
+    }
+    if !output.status.success() {
+        return Err(VerilatorError::VerilateFailed {
+            stderr,
+            diagnostics,
+        });
+    }
+    Ok(diagnostics)
+}
+
+/// Whether the `CARGO_ENCODED_RUSTFLAGS` `flags` link with
+/// `-Clinker-plugin-lto`, so the linker can optimize LLVM bitcode across
+/// languages.
+fn linker_plugin_lto(flags: &str) -> bool {
+    let mut enabled = false;
+    let mut codegen = false;
+    for flag in flags.split('\x1f') {
+        let opt = match flag.strip_prefix("-C") {
+            Some("") => {
+                codegen = true;
+                continue;
+            }
+            Some(opt) => opt,
+            None if codegen => flag,
+            None => continue,
+        };
+        codegen = false;
+        match opt.strip_prefix("linker-plugin-lto") {
+            Some("") => enabled = true,
+            Some(value) if value.starts_with('=') => {
+                enabled = !matches!(value, "=no" | "=n" | "=off" | "=false")
+            }
+            _ => {}
+        }
     }
+    enabled
 }
 
-fn getenv_unwrap(v: &str) -> String {
-    match env::var(v) {
-        Ok(s) => s,
-        Err(..) => fail(&format!("environment variable `{}` not defined", v)),
+/// Quote `arg` for a `verilator -f` file, which splits arguments on
+/// whitespace and strips comments before honouring quotes.
+fn quote_arg<'a>(arg: &'a str) -> Cow<'a, str> {
+    let plain = !arg.is_empty()
+        && !arg.starts_with('#')
+        && !arg.contains("//")
+        && !arg.contains("/*")
+        && !arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '\\');
+    if plain {
+        return Cow::Borrowed(arg);
     }
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    let mut prev = None;
+    for c in arg.chars() {
+        // An escaped `/` or `*` cannot complete a comment opened by `/`.
+        let escape = match c {
+            '"' | '\\' => true,
+            '/' | '*' => prev == Some('/'),
+            _ => false,
+        };
+        if escape {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+        prev = Some(c);
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
 }
 
-fn fail(s: &str) -> ! {
+/// Whether `path` is a C or C++ source rather than Verilog, going by the
+/// extensions verilator itself compiles.
+pub(crate) fn is_cpp(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => matches!(ext, "c" | "cc" | "cpp" | "cxx" | "c++"),
+        None => false,
+    }
+}
+
+fn getenv(v: &str) -> Result<String, VerilatorError> {
+    env::var(v).map_err(|_| VerilatorError::MissingEnv(v.to_string()))
+}
+
+pub(crate) fn fail(s: &str) -> ! {
     panic!("\n{}\n\nbuild script failed, must exit now", s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(verilator: &Verilator) -> Vec<String> {
+        verilator
+            .args("top")
+            .unwrap()
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn default_args() {
+        let mut verilator = Verilator::default();
+        verilator.out_dir("out").file("rtl/top.v");
+        assert_eq!(
+            args(&verilator),
+            [
+                "--cc",
+                "--MMD",
+                "-Mdir",
+                "out/Vtop",
+                "--top-module",
+                "top",
+                "--prefix",
+                "Vtop",
+                "rtl/top.v",
+            ]
+        );
+    }
+
+    #[test]
+    fn option_args() {
+        let mut verilator = Verilator::default();
+        verilator
+            .out_dir("out")
+            .prefix("Vcore")
+            .file_with_standard("rtl/top.sv", Standard::SystemVerilog2012)
+            .with_coverage(true)
+            .with_trace(true)
+            .trace_depth(2)
+            .trace_params(false)
+            .with_threads(4)
+            .no_warn("WIDTH")
+            .warn_as_error("UNUSED")
+            .module("cells")
+            .include_dir("include")
+            .define("SIMULATION", None)
+            .define("WIDTH", Some("8"))
+            .parameter("DEPTH", Parameter::Int(16));
+        assert_eq!(
+            args(&verilator),
+            [
+                "--cc",
+                "--MMD",
+                "-Mdir",
+                "out/Vcore",
+                "--top-module",
+                "top",
+                "--prefix",
+                "Vcore",
+                "--coverage",
+                "--trace",
+                "--trace-depth",
+                "2",
+                "--no-trace-params",
+                "--threads",
+                "4",
+                "-Wno-width",
+                "-Werror-UNUSED",
+                "-y",
+                "cells",
+                "+incdir+include",
+                "+define+SIMULATION",
+                "+define+WIDTH=8",
+                "-GDEPTH=16",
+                "+1800-2012ext+sv",
+                "rtl/top.sv",
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_args() {
+        assert_eq!(quote_arg("rtl/top.v"), "rtl/top.v");
+        assert_eq!(quote_arg("+define+NAME=top"), "+define+NAME=top");
+        assert_eq!(quote_arg("/tmp/a b/top.v"), r#""/tmp/a b/top.v""#);
+        assert_eq!(
+            quote_arg(r#"+define+MSG="hi" \ there"#),
+            r#""+define+MSG=\"hi\" \\ there""#
+        );
+        assert_eq!(quote_arg("a//b/*c"), r#""a/\/b/\*c""#);
+        assert_eq!(quote_arg("#1"), r##""#1""##);
+        assert_eq!(quote_arg("it's"), r#""it's""#);
+        assert_eq!(quote_arg(""), r#""""#);
+    }
+
+    #[test]
+    fn cpp_sources() {
+        let mut verilator = Verilator::default();
+        verilator
+            .file("rtl/top.v")
+            .file("c/dpi.c")
+            .file("c/model.cc")
+            .file("c/bench.cpp")
+            .file("c/util.cxx")
+            .file("c/types.h");
+        assert_eq!(
+            verilator.cpp_files(&[]),
+            vec![
+                PathBuf::from("c/dpi.c"),
+                PathBuf::from("c/model.cc"),
+                PathBuf::from("c/bench.cpp"),
+                PathBuf::from("c/util.cxx"),
+            ]
+        );
+    }
+
+    #[test]
+    fn linker_plugin_lto_flags() {
+        assert!(!linker_plugin_lto(""));
+        assert!(!linker_plugin_lto("-Copt-level=3"));
+        assert!(linker_plugin_lto("-Clinker-plugin-lto"));
+        assert!(linker_plugin_lto("-C\x1flinker-plugin-lto"));
+        assert!(linker_plugin_lto(
+            "-Copt-level=3\x1f-Clinker-plugin-lto=yes"
+        ));
+        assert!(linker_plugin_lto(
+            "-Clinker-plugin-lto=/usr/lib/LLVMgold.so"
+        ));
+        assert!(!linker_plugin_lto("-Clinker-plugin-lto=no"));
+        assert!(!linker_plugin_lto(
+            "-Clinker-plugin-lto\x1f-Clinker-plugin-lto=off"
+        ));
+        assert!(!linker_plugin_lto("--cfg\x1flinker-plugin-lto"));
+        assert!(!linker_plugin_lto("-Clinker-plugin-lto-x"));
+    }
+
+    #[test]
+    fn parameter_values() {
+        assert_eq!(Parameter::from(-3).to_string(), "-3");
+        assert_eq!(Parameter::from(16u32).to_string(), "16");
+        assert_eq!(Parameter::from(1.5).to_string(), "1.5");
+        assert_eq!(Parameter::from(2.0).to_string(), "2.0");
+        assert_eq!(Parameter::from("core0").to_string(), r#""core0""#);
+        assert_eq!(
+            Parameter::from(r#"say "hi" \ bye"#).to_string(),
+            r#""say \"hi\" \\ bye""#
+        );
+        assert_eq!(Parameter::Sized(8, 0xf0).to_string(), "8'hf0");
+        assert_eq!(Parameter::from(true).to_string(), "1'h1");
+        assert_eq!(Parameter::from(false).to_string(), "1'h0");
+    }
+
+    #[test]
+    fn sized_parameters() {
+        assert!(Parameter::Sized(4, 15).fits());
+        assert!(!Parameter::Sized(4, 99).fits());
+        assert!(!Parameter::Sized(0, 0).fits());
+        assert!(Parameter::Sized(64, u64::MAX).fits());
+        assert!(Parameter::Sized(128, u64::MAX).fits());
+
+        let mut verilator = Verilator::default();
+        verilator
+            .out_dir("out")
+            .file("rtl/top.v")
+            .parameter("MASK", Parameter::Sized(4, 99));
+        match verilator.args("top") {
+            Err(VerilatorError::InvalidParameter(reason)) => {
+                assert_eq!(reason, "value 99 of `MASK` does not fit in 4 bits")
+            }
+            r => panic!("expected an invalid parameter, got {:?}", r),
+        }
+    }
+}
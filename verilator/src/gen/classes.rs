@@ -0,0 +1,216 @@
+//! Parsing of the `V<top>_classes.mk` makefile written by verilator.
+
+/// Variables which verilator's own makefiles pass to the compiler as
+/// `-D<var>=<value>`.
+const DEFINES: &[&str] = &[
+    "VM_COVERAGE",
+    "VM_SC",
+    "VM_TRACE",
+    "VM_TRACE_FST",
+    "VM_TRACE_VCD",
+];
+
+/// The generated sources and configuration of a verilated model.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Classes {
+    vars: Vec<(String, Vec<String>)>,
+}
+
+impl Classes {
+    /// Parse the `=` and `+=` assignments in `content`.
+    pub fn parse(content: &str) -> Classes {
+        let mut classes = Classes::default();
+        let content = content.replace("\\\r\n", " ").replace("\\\n", " ");
+        for line in content.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let (name, append, value) = match line.find('=') {
+                Some(pos) if line[..pos].ends_with('+') => {
+                    (&line[..pos - 1], true, &line[pos + 1..])
+                }
+                Some(pos) => (&line[..pos], false, &line[pos + 1..]),
+                None => continue,
+            };
+            let name = name.trim();
+            let words = value.split_whitespace().map(|w| w.to_string());
+            match classes.vars.iter_mut().find(|(n, _)| n == name) {
+                Some((_, v)) => {
+                    if !append {
+                        v.clear();
+                    }
+                    v.extend(words);
+                }
+                None => classes.vars.push((name.to_string(), words.collect())),
+            }
+        }
+        classes
+    }
+
+    /// The words assigned to `var`, or an empty slice if it is not set.
+    pub fn get(&self, var: &str) -> &[String] {
+        self.vars
+            .iter()
+            .find(|(n, _)| n == var)
+            .map(|(_, v)| &v[..])
+            .unwrap_or(&[])
+    }
+
+    /// Whether `var` is set to `1`.
+    pub fn flag(&self, var: &str) -> bool {
+        self.get(var).first().is_some_and(|v| v == "1")
+    }
+
+    /// The generated C++ files to compile, relative to the output directory.
+    pub fn generated_sources(&self) -> Vec<String> {
+        [
+            "VM_CLASSES_FAST",
+            "VM_CLASSES_SLOW",
+            "VM_SUPPORT_FAST",
+            "VM_SUPPORT_SLOW",
+        ]
+        .iter()
+        .flat_map(|var| self.get(var))
+        .map(|class| format!("{}.cpp", class))
+        .collect()
+    }
+
+    /// The verilator runtime sources, from `${VERILATOR_ROOT}/include`, the
+    /// model needs linked in.
+    pub fn runtime_sources(&self) -> Vec<String> {
+        ["VM_GLOBAL_FAST", "VM_GLOBAL_SLOW"]
+            .iter()
+            .flat_map(|var| self.get(var))
+            .map(|class| format!("{}.cpp", class))
+            .collect()
+    }
+
+    /// The `VM_*` preprocessor definitions the generated C++ expects.
+    pub fn defines(&self) -> Vec<(&'static str, &str)> {
+        DEFINES
+            .iter()
+            .filter_map(|&var| self.get(var).first().map(|v| (var, v.as_str())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Vtop_classes.mk` written by verilator 5.020 for `--trace`.
+    const CLASSES_MK: &str = "# Verilated -*- Makefile -*-
+# DESCRIPTION: Verilator output: Make include file with class lists
+
+### Switches...
+# C11 constructs required?  0/1 (always on now)
+VM_C11 = 1
+# Timing enabled?  0/1
+VM_TIMING = 0
+# Coverage output mode?  0/1 (from --coverage)
+VM_COVERAGE = 0
+# Parallel builds?  0/1 (from --output-split)
+VM_PARALLEL_BUILDS = 0
+# Tracing output mode?  0/1 (from --trace/--trace-fst)
+VM_TRACE = 1
+# Tracing output mode in VCD format?  0/1 (from --trace)
+VM_TRACE_VCD = 1
+# Tracing output mode in FST format?  0/1 (from --trace-fst)
+VM_TRACE_FST = 0
+
+### Object file lists...
+# Generated module classes, fast-path, compile with highest optimization
+VM_CLASSES_FAST += \\
+\tVtop \\
+\tVtop___024root__DepSet_h84412442__0 \\
+
+# Generated module classes, non-fast-path, compile with low/medium optimization
+VM_CLASSES_SLOW += \\
+\tVtop___024root__Slow \\
+\tVtop___024root__DepSet_h84412442__0__Slow \\
+
+# Generated support classes, fast-path, compile with highest optimization
+VM_SUPPORT_FAST += \\
+\tVtop__Trace__0 \\
+
+# Generated support classes, non-fast-path, compile with low/medium optimization
+VM_SUPPORT_SLOW += \\
+\tVtop__Syms \\
+\tVtop__Trace__0__Slow \\
+
+# Global classes, need linked once per executable, fast-path, compile with highest optimization
+VM_GLOBAL_FAST += \\
+\tverilated \\
+\tverilated_vcd_c \\
+\tverilated_threads \\
+
+# Global classes, need linked once per executable, non-fast-path, compile with low/medium optimization
+VM_GLOBAL_SLOW += \\
+
+
+# Verilated -*- Makefile -*-
+";
+
+    #[test]
+    fn continuation_lines() {
+        let classes = Classes::parse(CLASSES_MK);
+        assert_eq!(
+            classes.get("VM_SUPPORT_SLOW"),
+            ["Vtop__Syms", "Vtop__Trace__0__Slow"]
+        );
+        assert!(classes.get("VM_GLOBAL_SLOW").is_empty());
+        assert!(classes.get("VM_MISSING").is_empty());
+
+        let crlf = Classes::parse("VM_CLASSES_FAST = \\\r\n\tVtop \\\r\n\tVtop__1\r\n");
+        assert_eq!(crlf.get("VM_CLASSES_FAST"), ["Vtop", "Vtop__1"]);
+    }
+
+    #[test]
+    fn generated_sources() {
+        let classes = Classes::parse(CLASSES_MK);
+        assert_eq!(
+            classes.generated_sources(),
+            [
+                "Vtop.cpp",
+                "Vtop___024root__DepSet_h84412442__0.cpp",
+                "Vtop___024root__Slow.cpp",
+                "Vtop___024root__DepSet_h84412442__0__Slow.cpp",
+                "Vtop__Trace__0.cpp",
+                "Vtop__Syms.cpp",
+                "Vtop__Trace__0__Slow.cpp",
+            ]
+        );
+        assert_eq!(
+            classes.runtime_sources(),
+            [
+                "verilated.cpp",
+                "verilated_vcd_c.cpp",
+                "verilated_threads.cpp"
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_and_defines() {
+        let classes = Classes::parse(CLASSES_MK);
+        assert!(classes.flag("VM_TRACE"));
+        assert!(!classes.flag("VM_COVERAGE"));
+        assert!(!classes.flag("VM_MISSING"));
+        assert_eq!(
+            classes.defines(),
+            [
+                ("VM_COVERAGE", "0"),
+                ("VM_TRACE", "1"),
+                ("VM_TRACE_FST", "0"),
+                ("VM_TRACE_VCD", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn assignment_replaces() {
+        let classes = Classes::parse("VM_SC = 1\nVM_SC = 0\nVM_SC += 2\n");
+        assert_eq!(classes.get("VM_SC"), ["0", "2"]);
+    }
+}
@@ -0,0 +1,110 @@
+//! Parsing of the make-style dependency file written by `verilator --MMD`.
+
+use std::path::PathBuf;
+
+/// Return the prerequisites of every rule in `content`, in order and without
+/// duplicates.
+pub fn parse(content: &str) -> Vec<PathBuf> {
+    let mut deps: Vec<PathBuf> = Vec::new();
+    let content = content.replace("\\\r\n", " ").replace("\\\n", " ");
+    for line in content.lines() {
+        let prereqs = match find_separator(line) {
+            Some(pos) => &line[pos + 1..],
+            None => continue,
+        };
+        for dep in split_escaped(prereqs) {
+            let dep = PathBuf::from(dep);
+            if !deps.contains(&dep) {
+                deps.push(dep);
+            }
+        }
+    }
+    deps
+}
+
+/// Find the `:` separating targets from prerequisites, skipping drive
+/// letters such as `C:\`.
+fn find_separator(line: &str) -> Option<usize> {
+    line.char_indices()
+        .find(|&(i, c)| {
+            c == ':'
+                && match line[i + 1..].chars().next() {
+                    Some(next) => next.is_whitespace(),
+                    None => true,
+                }
+        })
+        .map(|(i, _)| i)
+}
+
+/// Split on whitespace, honouring `\ ` and `$$` escapes.
+fn split_escaped(s: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some(' ') | Some('#')) => {
+                word.extend(chars.next());
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                word.push(chars.next().unwrap());
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(word.split_off(0));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn line_continuations() {
+        let content =
+            "obj/Vtop.cpp obj/Vtop.h: \\\n  rtl/top.v \\\r\n  rtl/core.v\\\n  rtl/alu.v\n";
+        assert_eq!(
+            parse(content),
+            paths(&["rtl/top.v", "rtl/core.v", "rtl/alu.v"])
+        );
+    }
+
+    #[test]
+    fn escaped_spaces() {
+        let content = "obj/Vtop.cpp: /home/me/my\\ project/top.v cost$$.v tag\\#1.v\n";
+        assert_eq!(
+            parse(content),
+            paths(&["/home/me/my project/top.v", "cost$.v", "tag#1.v"])
+        );
+    }
+
+    #[test]
+    fn multiple_targets() {
+        let content = "obj/Vtop.cpp obj/Vtop__Syms.cpp obj/Vtop.h: rtl/top.v rtl/core.v\n\
+                       obj/Vtop__ver.d: rtl/core.v rtl/alu.v\n\
+                       \n\
+                       rtl/top.v:\n";
+        assert_eq!(
+            parse(content),
+            paths(&["rtl/top.v", "rtl/core.v", "rtl/alu.v"])
+        );
+    }
+
+    #[test]
+    fn drive_letters() {
+        let content = "C:\\obj\\Vtop.cpp: C:\\rtl\\top.v D:/rtl/core.v\n";
+        assert_eq!(parse(content), paths(&["C:\\rtl\\top.v", "D:/rtl/core.v"]));
+    }
+}
@@ -0,0 +1,368 @@
+//! Parsing of verilator `-f`/`-F` argument files.
+
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Options which consume the following argument, less their leading `-` or
+/// `--`, as listed by `verilator --help`.
+const OPTIONS_WITH_ARG: &[&str] = &[
+    "bin",
+    "build-dep-bin",
+    "CFLAGS",
+    "clk",
+    "comp-limit-blocks",
+    "comp-limit-members",
+    "comp-limit-parens",
+    "comp-limit-syms",
+    "compiler",
+    "converge-limit",
+    "coverage-max-width",
+    "debugi",
+    "decorations",
+    "default-language",
+    "diagnostics-sarif-output",
+    "dumpi-graph",
+    "dumpi-tree",
+    "error-limit",
+    "expand-limit",
+    "FI",
+    "gate-stmts",
+    "hierarchical-block",
+    "hierarchical-params-file",
+    "if-depth",
+    "inline-mult",
+    "instr-count-dpi",
+    "json-only-meta-output",
+    "json-only-output",
+    "l2-name",
+    "language",
+    "LDFLAGS",
+    "lib-create",
+    "main-top-name",
+    "make",
+    "MAKEFLAGS",
+    "max-num-width",
+    "Mdir",
+    "mod-prefix",
+    "module-recursion-depth",
+    "o",
+    "output-split",
+    "output-split-cfuncs",
+    "output-split-ctrace",
+    "pins-bv",
+    "pipe-filter",
+    "prefix",
+    "preproc-token-limit",
+    "protect-key",
+    "protect-lib",
+    "public-depth",
+    "reloop-limit",
+    "threads",
+    "threads-dpi",
+    "threads-max-mtasks",
+    "timescale",
+    "timescale-override",
+    "top",
+    "top-module",
+    "trace-depth",
+    "trace-max-array",
+    "trace-max-width",
+    "trace-threads",
+    "unroll-count",
+    "unroll-stmts",
+    "unused-regexp",
+    "waiver-output",
+    "x-assign",
+    "x-initial",
+    "xml-output",
+];
+
+/// Options followed by an optional count, such as `-j 4`.
+const OPTIONS_WITH_COUNT: &[&str] = &["build-jobs", "j", "verilate-jobs"];
+
+/// Everything referenced by a file list and the lists it includes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileList {
+    /// The file list itself followed by every nested `-f`/`-F` list.
+    pub lists: Vec<PathBuf>,
+    /// Source files, including `-v` library files.
+    pub sources: Vec<PathBuf>,
+    /// `-y` module directories.
+    pub module_directories: Vec<PathBuf>,
+    /// `+incdir+` include directories.
+    pub include_directories: Vec<PathBuf>,
+}
+
+impl FileList {
+    /// Parse the file list at `path`, following nested lists.
+    ///
+    /// When `relative` is set, as for `-F`, relative paths in the list are
+    /// resolved against the directory containing it. Otherwise, as for `-f`,
+    /// they are left relative to the current directory.
+    pub fn parse(path: &Path, relative: bool) -> io::Result<FileList> {
+        let mut list = FileList::default();
+        list.parse_file(path, relative, &mut HashSet::new())?;
+        Ok(list)
+    }
+
+    /// Parse the list at `path` unless it is one of the canonical paths in
+    /// `visited`, so lists including each other are only read once.
+    fn parse_file(
+        &mut self,
+        path: &Path,
+        relative: bool,
+        visited: &mut HashSet<PathBuf>,
+    ) -> io::Result<()> {
+        let mut content = String::new();
+        File::open(path)?.read_to_string(&mut content)?;
+        if !visited.insert(path.canonicalize()?) {
+            return Ok(());
+        }
+        self.lists.push(path.to_path_buf());
+
+        let base = if relative { path.parent() } else { None };
+        let resolve = |p: &str| match base {
+            Some(base) => base.join(p),
+            None => PathBuf::from(p),
+        };
+
+        let content = strip_comments(&content);
+        let mut tokens = content.split_whitespace().map(expand_env).peekable();
+        while let Some(token) = tokens.next() {
+            match token.as_str() {
+                "-f" | "-F" => {
+                    let nested = next_arg(&mut tokens, &token)?;
+                    self.parse_file(&resolve(&nested), token == "-F", visited)?;
+                }
+                "-v" => {
+                    let file = next_arg(&mut tokens, &token)?;
+                    self.sources.push(resolve(&file));
+                }
+                "-y" => {
+                    let dir = next_arg(&mut tokens, &token)?;
+                    self.module_directories.push(resolve(&dir));
+                }
+                t if t.starts_with("+incdir+") => {
+                    for dir in t["+incdir+".len()..].split('+').filter(|d| !d.is_empty()) {
+                        self.include_directories.push(resolve(dir));
+                    }
+                }
+                t if t.starts_with('-') => {
+                    let option = t.trim_start_matches('-');
+                    if OPTIONS_WITH_ARG.contains(&option) {
+                        next_arg(&mut tokens, &token)?;
+                    } else if OPTIONS_WITH_COUNT.contains(&option)
+                        && tokens.peek().is_some_and(|n| n.parse::<u32>().is_ok())
+                    {
+                        tokens.next();
+                    }
+                }
+                t if t.starts_with('+') => {}
+                t => self.sources.push(resolve(t)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn next_arg<I>(tokens: &mut I, option: &str) -> io::Result<String>
+where
+    I: Iterator<Item = String>,
+{
+    tokens.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("missing argument to `{}` in file list", option),
+        )
+    })
+}
+
+/// Remove `//` and `/* */` comments.
+fn strip_comments(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    loop {
+        match (rest.find("//"), rest.find("/*")) {
+            (Some(l), b) if b.is_none() || Some(l) < b => {
+                out.push_str(&rest[..l]);
+                rest = match rest[l..].find('\n') {
+                    Some(end) => &rest[l + end..],
+                    None => "",
+                };
+            }
+            (_, Some(b)) => {
+                out.push_str(&rest[..b]);
+                out.push(' ');
+                rest = match rest[b + 2..].find("*/") {
+                    Some(end) => &rest[b + 2 + end + 2..],
+                    None => "",
+                };
+            }
+            _ => {
+                out.push_str(rest);
+                return out;
+            }
+        }
+    }
+}
+
+/// Expand `$VAR`, `${VAR}` and `$(VAR)` references to environment variables.
+fn expand_env(token: &str) -> String {
+    let mut out = String::with_capacity(token.len());
+    let mut rest = token;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = match after.chars().next() {
+            Some(open @ '{') | Some(open @ '(') => {
+                let close = if open == '{' { '}' } else { ')' };
+                match after.find(close) {
+                    Some(end) => (&after[1..end], end + 1),
+                    None => (&after[1..], after.len()),
+                }
+            }
+            _ => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        if name.is_empty() {
+            out.push('$');
+        } else {
+            out.push_str(&env::var(name).unwrap_or_default());
+        }
+        rest = &after[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process};
+
+    /// Write `files` into a fresh directory named after `test` and this
+    /// process, so concurrent test runs do not share it.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("verilator-filelist-{}-{}", process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        for &(name, content) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn nested_lists() {
+        let dir = write_files(
+            "nested",
+            &[
+                ("top.f", "top.v\n-F ip/ip.f\n-f ip/cwd.f\n"),
+                ("ip/ip.f", "core.v -v lib/cells.v\n"),
+                ("ip/cwd.f", "rtl/cwd.v\n"),
+            ],
+        );
+
+        let list = FileList::parse(&dir.join("top.f"), true).unwrap();
+        assert_eq!(
+            list.lists,
+            vec![dir.join("top.f"), dir.join("ip/ip.f"), dir.join("ip/cwd.f")]
+        );
+        assert_eq!(
+            list.sources,
+            vec![
+                dir.join("top.v"),
+                dir.join("ip/core.v"),
+                dir.join("ip/lib/cells.v"),
+                PathBuf::from("rtl/cwd.v"),
+            ]
+        );
+    }
+
+    #[test]
+    fn directories_and_defines() {
+        let dir = write_files(
+            "dirs",
+            &[(
+                "top.f",
+                "+incdir+include+vendor/include\n+define+SIMULATION+WIDTH=4\n-y cells\n\
+                 --top-module top -Wno-WIDTH top.v\n",
+            )],
+        );
+
+        let list = FileList::parse(&dir.join("top.f"), true).unwrap();
+        assert_eq!(
+            list.include_directories,
+            vec![dir.join("include"), dir.join("vendor/include")]
+        );
+        assert_eq!(list.module_directories, vec![dir.join("cells")]);
+        assert_eq!(list.sources, vec![dir.join("top.v")]);
+    }
+
+    #[test]
+    fn comments() {
+        let dir = write_files(
+            "comments",
+            &[(
+                "top.f",
+                "// the top level\ntop.v // not_a_source.v\n/* old.v\n   older.v */ new.v\n",
+            )],
+        );
+
+        let list = FileList::parse(&dir.join("top.f"), false).unwrap();
+        assert_eq!(
+            list.sources,
+            vec![PathBuf::from("top.v"), PathBuf::from("new.v")]
+        );
+    }
+
+    #[test]
+    fn missing_argument() {
+        let dir = write_files("missing", &[("top.f", "top.v -y\n")]);
+        let err = FileList::parse(&dir.join("top.f"), false).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn option_arguments() {
+        let dir = write_files(
+            "options",
+            &[(
+                "top.f",
+                "--timescale 1ns/1ps -default-language 1800-2017 --trace-depth 2\n\
+                 --unroll-count 64 --compiler clang --build-jobs 4 -j top.v\n\
+                 -CFLAGS -O2 --x-assign fast -j 8 core.v\n",
+            )],
+        );
+
+        let list = FileList::parse(&dir.join("top.f"), false).unwrap();
+        assert_eq!(
+            list.sources,
+            vec![PathBuf::from("top.v"), PathBuf::from("core.v")]
+        );
+    }
+
+    #[test]
+    fn recursive_lists() {
+        let dir = write_files(
+            "recursive",
+            &[
+                ("top.f", "top.v -F ip/ip.f -F top.f\n"),
+                ("ip/ip.f", "core.v -F ../top.f\n"),
+            ],
+        );
+
+        let list = FileList::parse(&dir.join("top.f"), true).unwrap();
+        assert_eq!(list.lists, vec![dir.join("top.f"), dir.join("ip/ip.f")]);
+        assert_eq!(list.sources, vec![dir.join("top.v"), dir.join("ip/core.v")]);
+    }
+}
@@ -0,0 +1,72 @@
+//! The configuration of the verilator runtime compiled by the `verilated`
+//! crate, published to dependent build scripts through its
+//! `links = "verilated"` metadata as `DEP_VERILATED_*` variables.
+
+use std::env;
+use std::path::PathBuf;
+use Version;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Runtime {
+    /// `${VERILATOR_ROOT}` the runtime was compiled from, unless it links a
+    /// prebuilt runtime without a verilator installation.
+    pub root: Option<PathBuf>,
+    /// Version of verilator the runtime was compiled from.
+    pub version: Version,
+    /// Whether the multithreaded runtime is linked.
+    pub threads: bool,
+    /// Whether the coverage runtime is linked.
+    pub coverage: bool,
+    /// Whether the DPI runtime is linked.
+    pub dpi: bool,
+    /// Whether the save/restore runtime is linked.
+    pub save: bool,
+    /// Whether the VCD runtime is linked.
+    pub vcd: bool,
+    /// Whether the VPI runtime is linked.
+    pub vpi: bool,
+    /// Whether the FST runtime is linked.
+    pub fst: bool,
+    /// Preprocessor definitions the runtime was compiled with. Those which
+    /// are not `VM_*` flags of the model must be compiled into it too.
+    pub defines: Vec<(String, Option<String>)>,
+}
+
+impl Runtime {
+    /// Read the runtime configuration, if the crate being built depends on
+    /// `verilated`.
+    pub fn from_env() -> Option<Runtime> {
+        let root = env::var_os("DEP_VERILATED_ROOT");
+        let version = env::var("DEP_VERILATED_VERSION").ok()?.parse().ok()?;
+        let flag = |v: &str| env::var(v).is_ok_and(|v| v == "1");
+        let defines = env::var("DEP_VERILATED_DEFINES")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|d| match d.find('=') {
+                Some(pos) => (d[..pos].to_string(), Some(d[pos + 1..].to_string())),
+                None => (d.to_string(), None),
+            })
+            .collect();
+        Some(Runtime {
+            root: root.map(PathBuf::from),
+            version,
+            threads: flag("DEP_VERILATED_THREADS"),
+            coverage: flag("DEP_VERILATED_COVERAGE"),
+            dpi: flag("DEP_VERILATED_DPI"),
+            save: flag("DEP_VERILATED_SAVE"),
+            vcd: flag("DEP_VERILATED_VCD"),
+            vpi: flag("DEP_VERILATED_VPI"),
+            fst: flag("DEP_VERILATED_FST"),
+            defines,
+        })
+    }
+
+    /// The value of the preprocessor definition `var` the runtime was
+    /// compiled with, `1` if it was defined without one.
+    pub fn define(&self, var: &str) -> Option<&str> {
+        self.defines
+            .iter()
+            .find(|(v, _)| v == var)
+            .map(|(_, val)| val.as_deref().unwrap_or("1"))
+    }
+}
@@ -0,0 +1,341 @@
+//! Discovery of a verilator installation.
+
+use regex::Regex;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::{from_utf8, FromStr};
+use std::{env, fmt, io};
+
+use {check_verilator_bin, check_verilator_root, has_verilated_context};
+
+/// A verilator version, such as `5.020`, as `major.minor.patch`.
+///
+/// Verilator numbers releases `major.minor`, so `patch` is zero unless
+/// explicitly given.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse the output of `verilator --version`, such as
+    /// `Verilator 5.020 2024-01-01 rev v5.020`.
+    pub fn parse_version_output(output: &str) -> Option<Version> {
+        let re = Regex::new(r"^Verilator (\d+)\.(\d+)(?:\.(\d+))?")
+            .expect("Failed to create version regex");
+        let captures = re.captures(output.trim_start())?;
+        Some(Version {
+            major: captures[1].parse().ok()?,
+            minor: captures[2].parse().ok()?,
+            patch: match captures.get(3) {
+                Some(patch) => patch.as_str().parse().ok()?,
+                None => 0,
+            },
+        })
+    }
+}
+
+/// Formats the version as verilator numbers its releases, such as `5.020`,
+/// with the patch level only when there is one.
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:03}", self.major, self.minor)?;
+        if self.patch != 0 {
+            write!(f, ".{}", self.patch)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    /// Parse `major.minor` or `major.minor.patch`, such as `4.200` or
+    /// `5.020.1`.
+    fn from_str(s: &str) -> Result<Version, String> {
+        let parts = s
+            .trim()
+            .split('.')
+            .map(|p| p.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("invalid version `{}`: {}", s, e))?;
+        match parts[..] {
+            [major, minor] => Ok(Version::new(major, minor, 0)),
+            [major, minor, patch] => Ok(Version::new(major, minor, patch)),
+            _ => Err(format!("invalid version `{}`", s)),
+        }
+    }
+}
+
+/// Optional parts of verilator an installation provides.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Multithreaded models, with `--threads`.
+    pub threads: bool,
+    /// FST waveforms, with `--trace-fst`.
+    pub fst: bool,
+    /// Timing control in the design, with `--timing`.
+    pub timing: bool,
+    /// `VerilatedContext`, replacing the static `Verilated::` state.
+    pub context: bool,
+}
+
+/// Errors reported by
+/// [`VerilatorInstall::discover`](struct.VerilatorInstall.html#method.discover).
+#[derive(Debug)]
+pub enum InstallError {
+    /// `verilator_bin` could not be found or run.
+    NotFound(PathBuf, Option<io::Error>),
+    /// The root does not contain the verilator runtime sources.
+    InvalidRoot(PathBuf),
+    /// The version reported by `verilator_bin --version` could not be parsed.
+    UnknownVersion(String),
+    /// The installation is older than required.
+    TooOld { found: Version, required: Version },
+}
+
+impl fmt::Display for InstallError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InstallError::NotFound(ref bin, _) => write!(
+                f,
+                "failed to execute `{}`\nis `verilator` not installed?",
+                bin.display()
+            ),
+            InstallError::InvalidRoot(ref root) => write!(
+                f,
+                "`{}` is not a verilator root: `include/verilated.cpp` not found",
+                root.display()
+            ),
+            InstallError::UnknownVersion(ref output) => {
+                write!(
+                    f,
+                    "failed to parse verilator version from `{}`",
+                    output.trim()
+                )
+            }
+            InstallError::TooOld { found, required } => write!(
+                f,
+                "verilator {} is installed, but at least {} is required",
+                found, required
+            ),
+        }
+    }
+}
+
+impl Error for InstallError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            InstallError::NotFound(_, Some(ref e)) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A verilator installation, with a root and binary that belong together.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerilatorInstall {
+    /// `${VERILATOR_ROOT}`, containing `include/verilated.cpp`.
+    pub root: PathBuf,
+    /// The `verilator_bin` executable.
+    pub bin: PathBuf,
+    /// The version reported by `bin`.
+    pub version: Version,
+    pub capabilities: Capabilities,
+}
+
+impl VerilatorInstall {
+    /// Find the installation named by `${VERILATOR_ROOT}` or, if it is not
+    /// set, the one `verilator_bin` on `PATH` belongs to.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use verilator::{Version, VerilatorInstall};
+    ///
+    /// let install = VerilatorInstall::discover()
+    ///     .and_then(|install| install.require(Version::new(4, 200, 0)))
+    ///     .unwrap();
+    /// println!("verilator {} in {}", install.version, install.root.display());
+    /// ```
+    pub fn discover() -> Result<VerilatorInstall, InstallError> {
+        match env::var_os("VERILATOR_ROOT") {
+            Some(root) => VerilatorInstall::from_root(root),
+            None => {
+                let bin = find_bin()
+                    .ok_or_else(|| InstallError::NotFound(PathBuf::from("verilator_bin"), None))?;
+                VerilatorInstall::new(bin_root(&bin)?, bin)
+            }
+        }
+    }
+
+    /// Use the installation at `root`, with `root/bin/verilator_bin` or, for
+    /// installs which keep it elsewhere, the `verilator_bin` on `PATH` if it
+    /// reports `root` as its own `${VERILATOR_ROOT}`.
+    pub fn from_root<P>(root: P) -> Result<VerilatorInstall, InstallError>
+    where
+        P: AsRef<Path>,
+    {
+        let root = root.as_ref().to_path_buf();
+        let bin = root.join("bin/verilator_bin");
+        if bin.is_file() {
+            return VerilatorInstall::new(root, bin);
+        }
+        let path_bin = find_bin().ok_or_else(|| InstallError::NotFound(bin.clone(), None))?;
+        if bin_root(&path_bin)? != root.canonicalize().unwrap_or_else(|_| root.clone()) {
+            return Err(InstallError::NotFound(bin, None));
+        }
+        VerilatorInstall::new(root, path_bin)
+    }
+
+    fn new(root: PathBuf, bin: PathBuf) -> Result<VerilatorInstall, InstallError> {
+        if !check_verilator_root(&root) {
+            return Err(InstallError::InvalidRoot(root));
+        }
+        let mut cmd = Command::new(&bin);
+        cmd.env("VERILATOR_ROOT", &root).arg("--version");
+        let stdout = output(&mut cmd, &bin)?;
+        let version = match Version::parse_version_output(&stdout) {
+            Some(version) => version,
+            None => return Err(InstallError::UnknownVersion(stdout)),
+        };
+        let include = root.join("include");
+        let capabilities = Capabilities {
+            threads: version.major >= 5 || include.join("verilated_threads.cpp").is_file(),
+            fst: include.join("verilated_fst_c.cpp").is_file(),
+            timing: include.join("verilated_timing.cpp").is_file(),
+            context: has_verilated_context((version.major, version.minor)),
+        };
+        Ok(VerilatorInstall {
+            root,
+            bin,
+            version,
+            capabilities,
+        })
+    }
+
+    /// Fail unless the installation is at least version `min`.
+    pub fn require(self, min: Version) -> Result<VerilatorInstall, InstallError> {
+        if self.version < min {
+            return Err(InstallError::TooOld {
+                found: self.version,
+                required: min,
+            });
+        }
+        Ok(self)
+    }
+
+    /// A command running `verilator_bin` with `${VERILATOR_ROOT}` set to
+    /// this installation's root.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.bin);
+        cmd.env("VERILATOR_ROOT", &self.root);
+        cmd
+    }
+}
+
+/// The `verilator_bin` of `root` or, failing that, the one on `PATH`,
+/// without running it.
+#[cfg(feature = "gen")]
+pub(crate) fn locate_bin(root: Option<&Path>) -> PathBuf {
+    root.map(|root| root.join("bin/verilator_bin"))
+        .filter(|bin| bin.is_file())
+        .or_else(find_bin)
+        .unwrap_or_else(|| PathBuf::from("verilator_bin"))
+}
+
+fn find_bin() -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH").unwrap_or_default())
+        .find(|p| check_verilator_bin(p))
+        .map(|p| p.join("verilator_bin"))
+}
+
+/// The `${VERILATOR_ROOT}` `bin` was installed with, canonicalized when it
+/// exists.
+fn bin_root(bin: &Path) -> Result<PathBuf, InstallError> {
+    let mut cmd = Command::new(bin);
+    cmd.env_remove("VERILATOR_ROOT")
+        .arg("--getenv")
+        .arg("VERILATOR_ROOT");
+    let root = PathBuf::from(output(&mut cmd, bin)?.trim());
+    Ok(root.canonicalize().unwrap_or(root))
+}
+
+fn output(cmd: &mut Command, bin: &Path) -> Result<String, InstallError> {
+    let output = cmd
+        .output()
+        .map_err(|e| InstallError::NotFound(bin.to_path_buf(), Some(e)))?;
+    match from_utf8(&output.stdout) {
+        Ok(stdout) if output.status.success() => Ok(stdout.to_string()),
+        _ => Err(InstallError::NotFound(bin.to_path_buf(), None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InstallError, Version};
+
+    #[test]
+    fn parse_version_output() {
+        assert_eq!(
+            Version::parse_version_output("Verilator 4.200 2021-03-12 rev v4.200\n"),
+            Some(Version::new(4, 200, 0))
+        );
+        assert_eq!(
+            Version::parse_version_output("Verilator 5.020 2024-01-01 rev v5.020"),
+            Some(Version::new(5, 20, 0))
+        );
+        assert_eq!(
+            Version::parse_version_output("Verilator 5.1.2 devel"),
+            Some(Version::new(5, 1, 2))
+        );
+        assert_eq!(
+            Version::parse_version_output("verilator_bin: not found"),
+            None
+        );
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!("5.020".parse(), Ok(Version::new(5, 20, 0)));
+        assert_eq!("4.200".parse(), Ok(Version::new(4, 200, 0)));
+        assert_eq!(" 5.20.1 ".parse(), Ok(Version::new(5, 20, 1)));
+        assert!("5".parse::<Version>().is_err());
+        assert!("5.x".parse::<Version>().is_err());
+        assert!("5.0.0.0".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn ordering() {
+        assert!(Version::new(4, 200, 0) < Version::new(5, 20, 0));
+        assert!(Version::new(5, 20, 0) < Version::new(5, 20, 1));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Version::new(5, 20, 0).to_string(), "5.020");
+        assert_eq!(Version::new(4, 200, 0).to_string(), "4.200");
+        assert_eq!(Version::new(4, 38, 0).to_string(), "4.038");
+        assert_eq!(Version::new(5, 20, 1).to_string(), "5.020.1");
+        assert_eq!("5.020".parse::<Version>().unwrap().to_string(), "5.020");
+        let too_old = InstallError::TooOld {
+            found: Version::new(4, 200, 0),
+            required: Version::new(5, 20, 0),
+        };
+        assert_eq!(
+            too_old.to_string(),
+            "verilator 4.200 is installed, but at least 5.020 is required"
+        );
+    }
+}
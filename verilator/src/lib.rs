@@ -4,18 +4,27 @@ extern crate cc;
 extern crate fnv;
 extern crate regex;
 #[cfg(feature = "module")]
+extern crate roxmltree;
+#[cfg(feature = "module")]
 extern crate syn;
+#[cfg(feature = "manifest")]
+extern crate toml;
 
+#[cfg(feature = "gen")]
+pub mod diagnostic;
 #[cfg(feature = "gen")]
 pub mod gen;
+#[cfg(feature = "manifest")]
+pub mod manifest;
 #[cfg(feature = "module")]
 pub mod module;
 
-use regex::Regex;
-use std::env;
+mod install;
+
+pub use install::{Capabilities, InstallError, VerilatorInstall, Version};
+
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::str::from_utf8;
+use std::sync::OnceLock;
 
 fn check_verilator_bin(path: &Path) -> bool {
     path.join("verilator_bin").is_file()
@@ -25,38 +34,78 @@ fn check_verilator_root(root: &Path) -> bool {
     root.join("include/verilated.cpp").is_file()
 }
 
-/// Parse the version of verilator on `$PATH`
+/// The installation found by
+/// [`VerilatorInstall::discover`](struct.VerilatorInstall.html#method.discover),
+/// which is only run once per process.
+fn discovered() -> Option<&'static VerilatorInstall> {
+    static INSTALL: OnceLock<Option<VerilatorInstall>> = OnceLock::new();
+    INSTALL
+        .get_or_init(|| VerilatorInstall::discover().ok())
+        .as_ref()
+}
+
+/// Parse the version of the verilator found by
+/// [`VerilatorInstall::discover`](struct.VerilatorInstall.html#method.discover).
 pub fn verilator_version() -> Option<(u32, u32)> {
-    let re = Regex::new(r"^Verilator (\d{1}).(\d{3})").expect("Failed to create version regex");
-    let output = Command::new("verilator_bin")
-        .arg("--version")
-        .output()
-        .ok()?;
-    let stdout = from_utf8(&output.stdout).ok()?;
-    let captures = re.captures(stdout)?;
-    let major = captures[1].parse::<u32>().ok()?;
-    let minor = captures[2].parse::<u32>().ok()?;
-    Some((major, minor))
+    discovered().map(|install| (install.version.major, install.version.minor))
 }
 
+/// Whether a verilator version provides `VerilatedContext`, which replaces
+/// the static `Verilated::` state from 4.200 onwards.
+pub fn has_verilated_context((major, minor): (u32, u32)) -> bool {
+    major > 4 || (major == 4 && minor >= 200)
+}
+
+/// The root of the verilator found by
+/// [`VerilatorInstall::discover`](struct.VerilatorInstall.html#method.discover).
 pub fn find_verilator_root() -> Option<PathBuf> {
-    env::split_paths(&env::var_os("PATH").unwrap_or_default())
-        .find(|p| check_verilator_bin(p.as_ref()))
-        .and_then(|p| {
-            let mut cmd = Command::new(p.join("verilator_bin"));
-            cmd.arg("--getenv").arg("VERILATOR_ROOT");
-
-            cmd.output().ok().map(|output| {
-                // Report what verilator says is ${VERILATOR_ROOT}
-                PathBuf::from(String::from_utf8_lossy(&output.stdout).trim())
-            })
-        })
-        .or_else(|| env::var("VERILATOR_ROOT").map(PathBuf::from).ok())
-        .and_then(|p| {
-            if check_verilator_root(p.as_ref()) {
-                Some(p)
-            } else {
-                None
-            }
-        })
+    discovered().map(|install| install.root.clone())
+}
+
+/// Generate and build the model described by `[package.metadata.verilator]`
+/// in the `Cargo.toml` of the crate being built, returning the output
+/// directory.
+///
+/// If the table names a `module`, the FFI shims are generated from it and
+/// compiled along with the verilated C++, as by
+/// [`Verilator::rust_module`](gen/struct.Verilator.html#method.rust_module).
+///
+/// Panics if any step fails. See
+/// [`try_build_from_manifest`](fn.try_build_from_manifest.html) for a
+/// non-panicking version.
+///
+/// # Example
+///
+/// ```ignore
+/// // build.rs
+/// extern crate verilator;
+///
+/// fn main() {
+///     verilator::build_from_manifest();
+/// }
+/// ```
+#[cfg(feature = "manifest")]
+pub fn build_from_manifest() -> PathBuf {
+    match try_build_from_manifest() {
+        Ok(dst) => dst,
+        Err(e) => gen::fail(&e.to_string()),
+    }
+}
+
+/// Generate and build the model described by `[package.metadata.verilator]`
+/// in the `Cargo.toml` of the crate being built, returning the output
+/// directory or the reason the build failed.
+#[cfg(feature = "manifest")]
+pub fn try_build_from_manifest() -> Result<PathBuf, gen::VerilatorError> {
+    let dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .ok_or_else(|| gen::VerilatorError::MissingEnv("CARGO_MANIFEST_DIR".to_string()))?;
+    let path = PathBuf::from(dir).join("Cargo.toml");
+    println!("cargo:rerun-if-changed={}", path.display());
+    let manifest = match manifest::Manifest::load(&path) {
+        Ok(manifest) => manifest,
+        Err(error) => return Err(gen::VerilatorError::Manifest { path, error }),
+    };
+
+    let mut verilator = gen::Verilator::default();
+    manifest.configure(&mut verilator).try_build(manifest.top())
 }
@@ -0,0 +1,676 @@
+//! Configuration of verilator builds from the `[package.metadata.verilator]`
+//! table of a crate's `Cargo.toml`.
+//!
+//! ```toml
+//! [package.metadata.verilator]
+//! top = "top"
+//! # Name of the generated C++ classes, defaulting to `V<top>`
+//! prefix = "Vtop"
+//! # Rust source scanned for `#[module]` structs by `ModuleGenerator`
+//! module = "src/main.rs"
+//! # Or a struct generated from the ports of `top`, in `$OUT_DIR/Top.rs`
+//! bindings = "Top"
+//! clock = "clk_i"
+//! reset = "rst_i"
+//! # Standard of every Verilog source without one of its own
+//! standard = "verilog2001"
+//! sources = [
+//!     "rtl/counter.v",
+//!     { path = "rtl/top.sv", standard = "systemverilog2012" },
+//! ]
+//! file-lists = ["rtl/files.f"]
+//! file-lists-relative = ["ip/core/core.f"]
+//! module-dirs = ["rtl/lib"]
+//! include-dirs = ["rtl/include"]
+//! defines = { SIMULATION = true, FIFO_DEPTH = "16" }
+//! parameters = { WIDTH = 32, NAME = "core0" }
+//! coverage = true
+//! trace = true
+//! trace-fst = false
+//! trace-depth = 2
+//! threads = 2
+//! optimize = true
+//! # C++ code generation of the model, independent of the Cargo profile
+//! cpp-opt-level = 2
+//! cpp-debug = false
+//! native-cpu = true
+//! lto = true
+//! # or `pgo-generate`, naming the directory profiles are written to
+//! pgo-use = "profile"
+//! no-warn = ["width"]
+//! warn-as-error = ["unused"]
+//! # Panic in port setters when a value does not fit the port
+//! checked-ports = true
+//! ```
+//!
+//! Relative paths are relative to the directory containing `Cargo.toml`.
+//! `trace-structs`, `trace-params`, `trace-max-array` and `trace-max-width`
+//! are also accepted and map onto the `Verilator` methods of the same name.
+
+use gen::{is_cpp, Parameter, Pgo, Standard, Verilator};
+use module::ModuleGenerator;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+use toml::value::{Table, Value};
+
+/// Errors reported by [`Manifest::load`](struct.Manifest.html#method.load).
+#[derive(Debug)]
+pub enum ManifestError {
+    /// `Cargo.toml` could not be read.
+    Io(io::Error),
+    /// `Cargo.toml` is not valid TOML.
+    Parse(toml::de::Error),
+    /// A required key, or the `[package.metadata.verilator]` table itself,
+    /// is missing.
+    Missing(String),
+    /// A key has a value of the wrong type.
+    Invalid { key: String, expected: &'static str },
+    /// A key is not recognised, such as a misspelling.
+    Unknown(String),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ManifestError::Io(ref e) => write!(f, "failed to read manifest: {}", e),
+            ManifestError::Parse(ref e) => write!(f, "failed to parse manifest: {}", e),
+            ManifestError::Missing(ref key) => write!(f, "missing `{}`", key),
+            ManifestError::Invalid { ref key, expected } => {
+                write!(f, "`{}` should be {}", key, expected)
+            }
+            ManifestError::Unknown(ref key) => write!(f, "unknown key `{}`", key),
+        }
+    }
+}
+
+impl Error for ManifestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ManifestError::Io(ref e) => Some(e),
+            ManifestError::Parse(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ManifestError {
+    fn from(e: io::Error) -> ManifestError {
+        ManifestError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ManifestError {
+    fn from(e: toml::de::Error) -> ManifestError {
+        ManifestError::Parse(e)
+    }
+}
+
+/// The contents of a `[package.metadata.verilator]` table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+    top: String,
+    prefix: Option<String>,
+    module: Option<PathBuf>,
+    bindings: Option<String>,
+    clock: Option<String>,
+    reset: Option<String>,
+    sources: Vec<(PathBuf, Option<Standard>)>,
+    file_lists: Vec<(PathBuf, bool)>,
+    module_dirs: Vec<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    defines: Vec<(String, Option<String>)>,
+    parameters: Vec<(String, Parameter)>,
+    coverage: bool,
+    trace: bool,
+    trace_fst: bool,
+    trace_depth: Option<u32>,
+    trace_structs: bool,
+    trace_params: bool,
+    trace_max_array: Option<u32>,
+    trace_max_width: Option<u32>,
+    optimize: bool,
+    threads: Option<u32>,
+    cpp_opt_level: Option<u32>,
+    cpp_debug: Option<bool>,
+    native_cpu: bool,
+    lto: bool,
+    pgo: Option<Pgo>,
+    no_warn: Vec<String>,
+    warn_as_error: Vec<String>,
+    checked_ports: bool,
+}
+
+impl Manifest {
+    /// Read the `[package.metadata.verilator]` table of the `Cargo.toml` at
+    /// `path`.
+    pub fn load<P>(path: P) -> Result<Manifest, ManifestError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Manifest::parse(&content, dir)
+    }
+
+    /// Parse the `[package.metadata.verilator]` table of `content`,
+    /// resolving relative paths against `dir`.
+    pub fn parse(content: &str, dir: &Path) -> Result<Manifest, ManifestError> {
+        let root: Value = toml::from_str(content)?;
+        let table = root
+            .get("package")
+            .and_then(|p| p.get("metadata"))
+            .and_then(|m| m.get("verilator"))
+            .ok_or_else(|| ManifestError::Missing("[package.metadata.verilator]".to_string()))?;
+        let table = table
+            .as_table()
+            .ok_or_else(|| invalid("verilator", "a table"))?;
+
+        let mut manifest = Manifest {
+            top: String::new(),
+            prefix: None,
+            module: None,
+            bindings: None,
+            clock: None,
+            reset: None,
+            sources: Vec::new(),
+            file_lists: Vec::new(),
+            module_dirs: Vec::new(),
+            include_dirs: Vec::new(),
+            defines: Vec::new(),
+            parameters: Vec::new(),
+            coverage: false,
+            trace: false,
+            trace_fst: false,
+            trace_depth: None,
+            trace_structs: false,
+            trace_params: true,
+            trace_max_array: None,
+            trace_max_width: None,
+            optimize: false,
+            threads: None,
+            cpp_opt_level: None,
+            cpp_debug: None,
+            native_cpu: false,
+            lto: false,
+            pgo: None,
+            no_warn: Vec::new(),
+            warn_as_error: Vec::new(),
+            checked_ports: false,
+        };
+
+        let standard = match table.get("standard") {
+            Some(v) => Some(standard("standard", v)?),
+            None => None,
+        };
+
+        for (key, value) in table {
+            match key.as_str() {
+                "top" => manifest.top = string(key, value)?.to_string(),
+                "prefix" => manifest.prefix = Some(string(key, value)?.to_string()),
+                "module" => manifest.module = Some(dir.join(string(key, value)?)),
+                "bindings" => manifest.bindings = Some(string(key, value)?.to_string()),
+                "clock" => manifest.clock = Some(string(key, value)?.to_string()),
+                "reset" => manifest.reset = Some(string(key, value)?.to_string()),
+                "standard" => {}
+                "sources" => {
+                    for source in array(key, value)? {
+                        manifest.sources.push(match *source {
+                            Value::Table(ref t) => {
+                                let path = t.get("path").ok_or_else(|| {
+                                    ManifestError::Missing("sources.path".to_string())
+                                })?;
+                                let s = match t.get("standard") {
+                                    Some(s) => Some(self::standard("sources.standard", s)?),
+                                    None => standard,
+                                };
+                                (dir.join(string("sources.path", path)?), s)
+                            }
+                            ref v => {
+                                let path = dir.join(string(key, v)?);
+                                // The default standard does not apply to C++
+                                let s = standard.filter(|_| !is_cpp(&path));
+                                (path, s)
+                            }
+                        });
+                    }
+                }
+                "file-lists" | "file-lists-relative" => {
+                    let relative = key == "file-lists-relative";
+                    for list in strings(key, value)? {
+                        manifest.file_lists.push((dir.join(list), relative));
+                    }
+                }
+                "module-dirs" => {
+                    manifest.module_dirs =
+                        strings(key, value)?.iter().map(|d| dir.join(d)).collect()
+                }
+                "include-dirs" => {
+                    manifest.include_dirs =
+                        strings(key, value)?.iter().map(|d| dir.join(d)).collect()
+                }
+                "defines" => {
+                    for (var, val) in table_of(key, value)? {
+                        let val = match *val {
+                            Value::Boolean(false) => continue,
+                            Value::Boolean(true) => None,
+                            Value::String(ref s) => Some(s.clone()),
+                            Value::Integer(i) => Some(i.to_string()),
+                            _ => {
+                                return Err(invalid(
+                                    &format!("defines.{}", var),
+                                    "a string, integer or boolean",
+                                ))
+                            }
+                        };
+                        manifest.defines.push((var.clone(), val));
+                    }
+                }
+                "parameters" => {
+                    for (name, val) in table_of(key, value)? {
+                        let val = match *val {
+                            Value::Boolean(b) => Parameter::from(b),
+                            Value::Integer(i) => Parameter::Int(i),
+                            Value::Float(f) => Parameter::Real(f),
+                            Value::String(ref s) => Parameter::Str(s.clone()),
+                            _ => {
+                                return Err(invalid(
+                                    &format!("parameters.{}", name),
+                                    "a string, number or boolean",
+                                ))
+                            }
+                        };
+                        manifest.parameters.push((name.clone(), val));
+                    }
+                }
+                "coverage" => manifest.coverage = boolean(key, value)?,
+                "trace" => manifest.trace = boolean(key, value)?,
+                "trace-fst" => manifest.trace_fst = boolean(key, value)?,
+                "trace-depth" => manifest.trace_depth = Some(unsigned(key, value)?),
+                "trace-structs" => manifest.trace_structs = boolean(key, value)?,
+                "trace-params" => manifest.trace_params = boolean(key, value)?,
+                "trace-max-array" => manifest.trace_max_array = Some(unsigned(key, value)?),
+                "trace-max-width" => manifest.trace_max_width = Some(unsigned(key, value)?),
+                "optimize" => manifest.optimize = boolean(key, value)?,
+                "threads" => manifest.threads = Some(unsigned(key, value)?),
+                "cpp-opt-level" => manifest.cpp_opt_level = Some(unsigned(key, value)?),
+                "cpp-debug" => manifest.cpp_debug = Some(boolean(key, value)?),
+                "native-cpu" => manifest.native_cpu = boolean(key, value)?,
+                "lto" => manifest.lto = boolean(key, value)?,
+                "pgo-generate" => manifest.pgo = Some(Pgo::Generate(dir.join(string(key, value)?))),
+                "pgo-use" => manifest.pgo = Some(Pgo::Use(dir.join(string(key, value)?))),
+                "no-warn" => {
+                    manifest.no_warn = strings(key, value)?.iter().map(|w| w.to_string()).collect()
+                }
+                "warn-as-error" => {
+                    manifest.warn_as_error =
+                        strings(key, value)?.iter().map(|w| w.to_string()).collect()
+                }
+                "checked-ports" => manifest.checked_ports = boolean(key, value)?,
+                _ => return Err(ManifestError::Unknown(key.clone())),
+            }
+        }
+
+        if manifest.top.is_empty() {
+            return Err(ManifestError::Missing("top".to_string()));
+        }
+        Ok(manifest)
+    }
+
+    /// The name of the top-level Verilog module.
+    pub fn top(&self) -> &str {
+        &self.top
+    }
+
+    /// The Rust source scanned for `#[module]` structs, if any.
+    pub fn module(&self) -> Option<&Path> {
+        self.module.as_deref()
+    }
+
+    /// Apply the configuration, including the `module` whose shims or the
+    /// `bindings` which are generated and compiled with the model, to
+    /// `verilator`.
+    pub fn configure<'a>(&self, verilator: &'a mut Verilator) -> &'a mut Verilator {
+        for (file, standard) in &self.sources {
+            match *standard {
+                Some(standard) => verilator.file_with_standard(file, standard),
+                None => verilator.file(file),
+            };
+        }
+        if let Some(ref prefix) = self.prefix {
+            verilator.prefix(prefix);
+        }
+        if let Some(ref krate) = self.module {
+            verilator
+                .rust_module(krate)
+                .with_checked_ports(self.checked_ports);
+        }
+        if let Some(ref rs_ty) = self.bindings {
+            verilator
+                .rust_bindings(rs_ty)
+                .with_checked_ports(self.checked_ports);
+        }
+        if let Some(ref clock) = self.clock {
+            verilator.clock_port(clock);
+        }
+        if let Some(ref reset) = self.reset {
+            verilator.reset_port(reset);
+        }
+        for (list, relative) in &self.file_lists {
+            if *relative {
+                verilator.file_list_relative(list);
+            } else {
+                verilator.file_list(list);
+            }
+        }
+        for dir in &self.module_dirs {
+            verilator.module(dir);
+        }
+        for dir in &self.include_dirs {
+            verilator.include_dir(dir);
+        }
+        for (var, val) in &self.defines {
+            verilator.define(var, val.as_ref().map(|v| v.as_str()));
+        }
+        for (name, val) in &self.parameters {
+            verilator.parameter(name, val.clone());
+        }
+        verilator
+            .with_coverage(self.coverage)
+            .with_trace(self.trace)
+            .with_trace_fst(self.trace_fst)
+            .trace_structs(self.trace_structs)
+            .trace_params(self.trace_params)
+            .with_performance_optimizations(self.optimize);
+        if let Some(depth) = self.trace_depth {
+            verilator.trace_depth(depth);
+        }
+        if let Some(depth) = self.trace_max_array {
+            verilator.trace_max_array(depth);
+        }
+        if let Some(width) = self.trace_max_width {
+            verilator.trace_max_width(width);
+        }
+        if let Some(threads) = self.threads {
+            verilator.with_threads(threads);
+        }
+        if let Some(level) = self.cpp_opt_level {
+            verilator.cpp_opt_level(level);
+        }
+        if let Some(debug) = self.cpp_debug {
+            verilator.with_cpp_debug(debug);
+        }
+        verilator
+            .with_native_cpu(self.native_cpu)
+            .with_lto(self.lto);
+        if let Some(ref pgo) = self.pgo {
+            verilator.pgo(pgo.clone());
+        }
+        for warn in &self.no_warn {
+            verilator.no_warn(warn);
+        }
+        for warn in &self.warn_as_error {
+            verilator.warn_as_error(warn);
+        }
+        verilator
+    }
+
+    /// Apply the configuration to `generator`.
+    pub fn configure_module<'a>(
+        &self,
+        generator: &'a mut ModuleGenerator,
+    ) -> &'a mut ModuleGenerator {
+        if let Some(ref prefix) = self.prefix {
+            generator.prefix(prefix);
+        }
+        if let Some(ref clock) = self.clock {
+            generator.clock(clock);
+        }
+        if let Some(ref reset) = self.reset {
+            generator.reset(reset);
+        }
+        generator
+            .with_trace_fst(self.trace_fst)
+            .with_checked_ports(self.checked_ports)
+    }
+}
+
+fn invalid(key: &str, expected: &'static str) -> ManifestError {
+    ManifestError::Invalid {
+        key: key.to_string(),
+        expected,
+    }
+}
+
+fn string<'a>(key: &str, value: &'a Value) -> Result<&'a str, ManifestError> {
+    value.as_str().ok_or_else(|| invalid(key, "a string"))
+}
+
+fn boolean(key: &str, value: &Value) -> Result<bool, ManifestError> {
+    value.as_bool().ok_or_else(|| invalid(key, "a boolean"))
+}
+
+fn unsigned(key: &str, value: &Value) -> Result<u32, ManifestError> {
+    value
+        .as_integer()
+        .filter(|&i| i >= 0 && i <= i64::from(u32::MAX))
+        .map(|i| i as u32)
+        .ok_or_else(|| invalid(key, "a positive integer"))
+}
+
+fn array<'a>(key: &str, value: &'a Value) -> Result<&'a [Value], ManifestError> {
+    value
+        .as_array()
+        .map(|a| &a[..])
+        .ok_or_else(|| invalid(key, "an array"))
+}
+
+fn strings<'a>(key: &str, value: &'a Value) -> Result<Vec<&'a str>, ManifestError> {
+    array(key, value)?
+        .iter()
+        .map(|v| {
+            v.as_str()
+                .ok_or_else(|| invalid(key, "an array of strings"))
+        })
+        .collect()
+}
+
+fn table_of<'a>(key: &str, value: &'a Value) -> Result<&'a Table, ManifestError> {
+    value.as_table().ok_or_else(|| invalid(key, "a table"))
+}
+
+fn standard(key: &str, value: &Value) -> Result<Standard, ManifestError> {
+    match string(key, value)?.to_ascii_lowercase().as_str() {
+        "verilog1995" => Ok(Standard::Verilog1995),
+        "verilog2001" => Ok(Standard::Verilog2001),
+        "verilog2005" => Ok(Standard::Verilog2005),
+        "systemverilog2005" => Ok(Standard::SystemVerilog2005),
+        "systemverilog2009" => Ok(Standard::SystemVerilog2009),
+        "systemverilog2012" => Ok(Standard::SystemVerilog2012),
+        _ => Err(invalid(
+            key,
+            "one of verilog1995, verilog2001, verilog2005, systemverilog2005, \
+             systemverilog2009 or systemverilog2012",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(table: &str) -> Result<Manifest, ManifestError> {
+        let content = format!(
+            "[package]\nname = \"model\"\n\n[package.metadata.verilator]\n{}",
+            table
+        );
+        Manifest::parse(&content, Path::new("/crate"))
+    }
+
+    fn invalid_key(table: &str) -> String {
+        match parse(table) {
+            Err(ManifestError::Invalid { key, .. }) => key,
+            r => panic!("expected an invalid key, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn sources_and_paths() {
+        let manifest = parse(
+            "top = \"top\"\n\
+             prefix = \"Vcore\"\n\
+             module = \"src/main.rs\"\n\
+             standard = \"verilog2001\"\n\
+             sources = [\"rtl/counter.v\", \"c/dpi.cc\", \
+                 { path = \"rtl/top.sv\", standard = \"SystemVerilog2012\" }]\n\
+             file-lists = [\"rtl/files.f\"]\n\
+             file-lists-relative = [\"ip/core.f\"]\n\
+             module-dirs = [\"rtl/lib\"]\n\
+             include-dirs = [\"rtl/include\"]\n\
+             trace = true\n\
+             trace-depth = 2\n\
+             threads = 4\n\
+             pgo-use = \"profile\"\n\
+             no-warn = [\"width\"]\n",
+        )
+        .unwrap();
+
+        assert_eq!(manifest.top(), "top");
+        assert_eq!(manifest.prefix.as_deref(), Some("Vcore"));
+        assert_eq!(manifest.module(), Some(Path::new("/crate/src/main.rs")));
+        assert_eq!(
+            manifest.sources,
+            vec![
+                (
+                    PathBuf::from("/crate/rtl/counter.v"),
+                    Some(Standard::Verilog2001)
+                ),
+                (PathBuf::from("/crate/c/dpi.cc"), None),
+                (
+                    PathBuf::from("/crate/rtl/top.sv"),
+                    Some(Standard::SystemVerilog2012)
+                ),
+            ]
+        );
+        assert_eq!(
+            manifest.file_lists,
+            vec![
+                (PathBuf::from("/crate/rtl/files.f"), false),
+                (PathBuf::from("/crate/ip/core.f"), true),
+            ]
+        );
+        assert_eq!(manifest.module_dirs, vec![PathBuf::from("/crate/rtl/lib")]);
+        assert_eq!(
+            manifest.include_dirs,
+            vec![PathBuf::from("/crate/rtl/include")]
+        );
+        assert!(manifest.trace);
+        assert_eq!(manifest.trace_depth, Some(2));
+        assert_eq!(manifest.threads, Some(4));
+        assert_eq!(
+            manifest.pgo,
+            Some(Pgo::Use(PathBuf::from("/crate/profile")))
+        );
+        assert_eq!(manifest.no_warn, vec!["width".to_string()]);
+        assert!(manifest.trace_params);
+        assert!(!manifest.checked_ports);
+    }
+
+    #[test]
+    fn defines_and_parameters() {
+        let manifest = parse(
+            "top = \"top\"\n\
+             defines = { SIMULATION = true, SYNTHESIS = false, FIFO_DEPTH = 16, NAME = \"core\" }\n\
+             parameters = { WIDTH = 32, SCALE = 1.5, NAME = \"core0\", ENABLE = true }\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.defines,
+            vec![
+                ("FIFO_DEPTH".to_string(), Some("16".to_string())),
+                ("NAME".to_string(), Some("core".to_string())),
+                ("SIMULATION".to_string(), None),
+            ]
+        );
+        assert_eq!(
+            manifest.parameters,
+            vec![
+                ("ENABLE".to_string(), Parameter::Sized(1, 1)),
+                ("NAME".to_string(), Parameter::Str("core0".to_string())),
+                ("SCALE".to_string(), Parameter::Real(1.5)),
+                ("WIDTH".to_string(), Parameter::Int(32)),
+            ]
+        );
+    }
+
+    #[test]
+    fn bindings_clock_and_reset() {
+        let manifest = parse(
+            "top = \"top\"\n\
+             bindings = \"Top\"\n\
+             clock = \"clk_i\"\n\
+             reset = \"rst_i\"\n\
+             checked-ports = true\n",
+        )
+        .unwrap();
+
+        assert_eq!(manifest.bindings.as_deref(), Some("Top"));
+        assert_eq!(manifest.clock.as_deref(), Some("clk_i"));
+        assert_eq!(manifest.reset.as_deref(), Some("rst_i"));
+        assert!(manifest.checked_ports);
+        assert_eq!(manifest.module(), None);
+    }
+
+    #[test]
+    fn missing_keys() {
+        match parse("trace = true\n") {
+            Err(ManifestError::Missing(ref key)) if key == "top" => {}
+            r => panic!("expected a missing `top`, got {:?}", r),
+        }
+        match Manifest::parse("[package]\nname = \"model\"\n", Path::new("")) {
+            Err(ManifestError::Missing(ref key)) if key == "[package.metadata.verilator]" => {}
+            r => panic!("expected a missing table, got {:?}", r),
+        }
+        match parse("top = \"top\"\nsources = [{ standard = \"verilog2005\" }]\n") {
+            Err(ManifestError::Missing(ref key)) if key == "sources.path" => {}
+            r => panic!("expected a missing `sources.path`, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn unknown_key() {
+        match parse("top = \"top\"\ntrace_fst = true\n") {
+            Err(ManifestError::Unknown(ref key)) if key == "trace_fst" => {}
+            r => panic!("expected an unknown key, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn wrong_types() {
+        assert_eq!(invalid_key("top = 1\n"), "top");
+        assert_eq!(invalid_key("top = \"top\"\ntrace = \"yes\"\n"), "trace");
+        assert_eq!(invalid_key("top = \"top\"\nthreads = -1\n"), "threads");
+        assert_eq!(
+            invalid_key("top = \"top\"\nsources = \"top.v\"\n"),
+            "sources"
+        );
+        assert_eq!(
+            invalid_key("top = \"top\"\ninclude-dirs = [1]\n"),
+            "include-dirs"
+        );
+        assert_eq!(
+            invalid_key("top = \"top\"\nstandard = \"verilog2000\"\n"),
+            "standard"
+        );
+        assert_eq!(
+            invalid_key("top = \"top\"\ndefines = { DEPTH = 1.5 }\n"),
+            "defines.DEPTH"
+        );
+        assert_eq!(
+            invalid_key("top = \"top\"\nparameters = { TAPS = [1, 2] }\n"),
+            "parameters.TAPS"
+        );
+    }
+}
@@ -1,15 +1,25 @@
+mod xml;
+
+use self::xml::{Direction, VerilogPort};
+
 use fnv::FnvHashSet;
 
 use std::env;
-use std::fs::File;
-use std::io::BufWriter;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::prelude::*;
+use std::io::{self, BufWriter};
 use std::path::{Path, PathBuf};
 
-use syn::punctuated::Punctuated;
-use syn::token::Comma;
 use syn::visit::{self, Visit};
-use syn::{self, AttrStyle, Attribute, Fields, Generics, ItemStruct, Meta, NestedMeta, Visibility};
+use syn::{
+    self, AttrStyle, Attribute, Fields, Generics, Ident, ItemStruct, Meta, NestedMeta, Visibility,
+};
+
+#[cfg(feature = "manifest")]
+use manifest::{Manifest, ManifestError};
+use {has_verilated_context, verilator_version, Version};
 
 macro_rules! t {
     ($e:expr) => {
@@ -20,20 +30,112 @@ macro_rules! t {
     };
 }
 
+/// Errors reported while generating shims.
+#[derive(Debug)]
+pub enum ModuleError {
+    /// The crate or the netlist could not be read.
+    Io(io::Error),
+    /// The crate is not valid Rust.
+    Parse(syn::Error),
+    /// The netlist does not describe the ports of the top module.
+    Netlist(String),
+    /// The prefix is not that of a verilated model, `V<name>`.
+    Prefix(String),
+    /// The clock or reset is not a single bit input of the top module.
+    Port(String),
+    /// A `#[port]` field is invalid, prefixed with its file and line.
+    Field(String),
+    /// The `#[port]` fields of `rs_ty` do not match the ports of `top`, one
+    /// error per field, prefixed with its file and line.
+    PortMismatch {
+        rs_ty: String,
+        top: String,
+        errors: Vec<String>,
+    },
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModuleError::Io(ref e) => write!(f, "failed to read file: {}", e),
+            ModuleError::Parse(ref e) => write!(f, "failed to parse crate: {}", e),
+            ModuleError::Netlist(ref reason) => write!(f, "invalid netlist: {}", reason),
+            ModuleError::Prefix(ref prefix) => {
+                write!(f, "prefix `{}` does not start with `V`", prefix)
+            }
+            ModuleError::Port(ref reason) => f.write_str(reason),
+            ModuleError::Field(ref reason) => write!(f, "invalid port: {}", reason),
+            ModuleError::PortMismatch {
+                ref rs_ty,
+                ref top,
+                ref errors,
+            } => write!(
+                f,
+                "the ports of `{}` do not match the verilated `{}`:\n{}",
+                rs_ty,
+                top,
+                errors.join("\n")
+            ),
+        }
+    }
+}
+
+impl Error for ModuleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ModuleError::Io(ref e) => Some(e),
+            ModuleError::Parse(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModuleError {
+    fn from(e: io::Error) -> ModuleError {
+        ModuleError::Io(e)
+    }
+}
+
+impl From<syn::Error> for ModuleError {
+    fn from(e: syn::Error) -> ModuleError {
+        ModuleError::Parse(e)
+    }
+}
+
 /// A builder used to generate verilator FFI shim.
 pub struct ModuleGenerator {
     out_dir: Option<PathBuf>,
     target: Option<String>,
+    prefix: Option<String>,
+    trace_vcd: Option<bool>,
+    trace_fst: bool,
+    checked_ports: bool,
+    context: Option<bool>,
+    clock: Option<String>,
+    reset: Option<String>,
+    netlist: Option<(PathBuf, String)>,
 }
 
 impl ModuleGenerator {
+    /// Create a generator configured from the `[package.metadata.verilator]`
+    /// table of the `Cargo.toml` at `path`.
+    #[cfg(feature = "manifest")]
+    pub fn from_manifest<P>(path: P) -> Result<ModuleGenerator, ManifestError>
+    where
+        P: AsRef<Path>,
+    {
+        let mut generator = ModuleGenerator::default();
+        Manifest::load(path)?.configure_module(&mut generator);
+        Ok(generator)
+    }
+
     /// Configures the output directory of the generated Rust and C code.
     ///
     /// Note that for Cargo builds this defaults to `$OUT_DIR` and it's not
     /// necessary to call.
     ///
     /// ```ignore
-    /// use verilated::gen::ModuleGenerator;
+    /// use verilator::module::ModuleGenerator;
     ///
     /// let mut cfg = ModuleGenerator::default();
     /// cfg.out_dir("path/to/output");
@@ -51,40 +153,188 @@ impl ModuleGenerator {
         self
     }
 
-    /// Generate shim.
+    /// Only generate shims for the model verilated with `--prefix prefix`,
+    /// that is the structs annotated `#[module(name)]` where `prefix` is
+    /// `V<name>`.
+    pub fn prefix(&mut self, prefix: &str) -> &mut ModuleGenerator {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Generate `open_trace()` for models traced to VCD.
+    ///
+    /// Defaults to whether the `verilated` crate was built with its `vcd`
+    /// feature, or `true` outside of a crate depending on it.
+    pub fn with_trace_vcd(&mut self, t: bool) -> &mut ModuleGenerator {
+        self.trace_vcd = Some(t);
+        self
+    }
+
+    /// Generate `open_trace_fst()` for models verilated with
+    /// `--trace-fst`.
+    ///
+    /// Requires the `fst` feature of the `verilated` crate.
+    pub fn with_trace_fst(&mut self, t: bool) -> &mut ModuleGenerator {
+        self.trace_fst = t;
+        self
+    }
+
+    /// Generate shims which construct models against a `VerilatedContext`,
+    /// available since Verilator 4.200.
+    ///
+    /// Defaults to whether the verilator the `verilated` crate was built
+    /// with provides it, or outside of a crate depending on it, the
+    /// verilator found by `VerilatorInstall::discover`.
+    pub fn with_context(&mut self, t: bool) -> &mut ModuleGenerator {
+        self.context = Some(t);
+        self
+    }
+
+    /// Generate setters which panic when a value does not fit in the width
+    /// of the port, rather than truncating it.
+    pub fn with_checked_ports(&mut self, t: bool) -> &mut ModuleGenerator {
+        self.checked_ports = t;
+        self
+    }
+
+    /// Check the `#[port]` fields of every struct against the ports of the
+    /// module `top` in the netlist written by `verilator --xml-only`.
+    ///
+    /// Fields which are not ports of `top`, or whose direction or width
+    /// differ from the Verilog, fail generation with the line of each field.
+    /// Inputs of `top` without a field are reported as cargo warnings.
+    pub fn netlist<P>(&mut self, xml: P, top: &str) -> &mut ModuleGenerator
+    where
+        P: AsRef<Path>,
+    {
+        self.netlist = Some((xml.as_ref().to_owned(), top.to_string()));
+        self
+    }
+
+    /// The input of the top module toggled by `clock_toggle()` of bindings
+    /// generated by `generate_bindings`.
+    pub fn clock(&mut self, port: &str) -> &mut ModuleGenerator {
+        self.clock = Some(port.to_string());
+        self
+    }
+
+    /// The input of the top module toggled by `reset_toggle()` of bindings
+    /// generated by `generate_bindings`.
+    pub fn reset(&mut self, port: &str) -> &mut ModuleGenerator {
+        self.reset = Some(port.to_string());
+        self
+    }
+
+    /// Generate the shim for the module `top` as `rs_ty` from its ports in
+    /// the netlist written by `verilator --xml-only`, without a `#[module]`
+    /// struct, returning the C++ file generated.
+    ///
+    /// Every port of `top` gets an accessor, sized and signed as declared.
+    /// The Rust is written to `<out_dir>/<rs_ty>.rs`, to be included with
+    /// `include!`.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// use verilator::module::ModuleGenerator;
+    ///
+    /// let mut cfg = ModuleGenerator::default();
+    /// cfg.clock("clk_i").generate_bindings("obj_dir/Vtop.xml", "top", "Top")?;
+    /// ```
+    pub fn generate_bindings<P>(
+        &mut self,
+        xml: P,
+        top: &str,
+        rs_ty: &str,
+    ) -> Result<PathBuf, ModuleError>
+    where
+        P: AsRef<Path>,
+    {
+        let xml = xml.as_ref();
+        let ports = read_netlist(xml, top)?;
+        let c_ty = self.model_name(top)?;
+        let ports = self.bindings_ports(&ports)?;
+
+        let out_dir = self.dst();
+        let mut gen = self.generator(&out_dir, xml, None);
+        gen.gen_module(rs_ty, c_ty, ports)
+    }
+
+    /// The C++ file [`generate_bindings`](#method.generate_bindings) writes
+    /// for the module `top`, without generating it.
+    pub fn bindings_file(&self, top: &str) -> Result<PathBuf, ModuleError> {
+        Ok(cpp_file(&self.dst(), self.model_name(top)?))
+    }
+
+    /// The name of the model of the module `top`, that is the prefix it was
+    /// verilated with less the `V`.
+    fn model_name<'a>(&'a self, top: &'a str) -> Result<&'a str, ModuleError> {
+        match self.prefix {
+            Some(ref prefix) if prefix.starts_with('V') => Ok(&prefix[1..]),
+            Some(ref prefix) => Err(ModuleError::Prefix(prefix.clone())),
+            None => Ok(top),
+        }
+    }
+
+    /// Generate shim, returning the C++ file generated for each module.
     ///
     /// # Examples
     ///
     /// ```ignore
-    /// use verilated::gen::ModuleGenerator;
+    /// use verilator::module::ModuleGenerator;
     ///
-    /// let mut cfg = ModuleGenerator::new();
+    /// let mut cfg = ModuleGenerator::default();
     /// cfg.generate("../path/to/lib.rs");
     /// ```
-    pub fn generate<P>(&mut self, krate: P)
+    ///
+    /// # Panics
+    ///
+    /// Panics if generation fails. Use
+    /// [`try_generate`](#method.try_generate) to handle the error instead.
+    pub fn generate<P>(&mut self, krate: P) -> Vec<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        match self.try_generate(krate) {
+            Ok(files) => files,
+            Err(e) => panic!("\n\n{}\n\n", e),
+        }
+    }
+
+    /// Generate shim, returning the C++ file generated for each module, or
+    /// the reason generation failed.
+    pub fn try_generate<P>(&mut self, krate: P) -> Result<Vec<PathBuf>, ModuleError>
     where
         P: AsRef<Path>,
     {
         self._generate(krate.as_ref())
     }
 
-    fn _generate(&mut self, krate: &Path) {
-        self._generate_files(krate);
+    /// The C++ files [`try_generate`](#method.try_generate) writes for
+    /// `krate`, without generating them.
+    pub fn shim_files<P>(&self, krate: P) -> Result<Vec<PathBuf>, ModuleError>
+    where
+        P: AsRef<Path>,
+    {
+        self._generate_files(krate.as_ref(), true)
+    }
+
+    fn _generate(&mut self, krate: &Path) -> Result<Vec<PathBuf>, ModuleError> {
+        self._generate_files(krate, false)
     }
 
-    fn _generate_files(&mut self, krate: &Path) {
-        let mut file = File::open(krate).expect("Unable to open file");
+    fn _generate_files(&self, krate: &Path, dry_run: bool) -> Result<Vec<PathBuf>, ModuleError> {
+        let mut file = File::open(krate)?;
         let mut content = String::new();
-        file.read_to_string(&mut content)
-            .expect("Unable to read file");
+        file.read_to_string(&mut content)?;
 
-        let ast = syn::parse_file(&content).expect("Unable to parse file");
-        println!("{} items", ast.items.len());
+        let ast = syn::parse_file(&content)?;
+        if !dry_run {
+            println!("{} items", ast.items.len());
+        }
 
         // Prep the code generator
-        let out_dir = self.out_dir
-            .clone()
-            .unwrap_or_else(|| PathBuf::from(env::var_os("OUT_DIR").unwrap()));
+        let out_dir = self.dst();
 
         // Probe the crate to find all the structs of interest
         let mut structs = StructFinder {
@@ -92,14 +342,107 @@ impl ModuleGenerator {
         };
         visit::visit_file(&mut structs, &ast);
 
-        let mut gen = Generator {
-            out_dir: &out_dir,
-            krate,
-            found_module: false,
+        let netlist = match self.netlist {
+            Some((ref xml, ref top)) if !dry_run => Some((top.as_str(), read_netlist(xml, top)?)),
+            _ => None,
         };
+        let netlist = netlist.as_ref().map(|(top, ports)| (*top, &ports[..]));
+        let mut gen = self.generator(&out_dir, krate, netlist);
+        gen.dry_run = dry_run;
 
         // Walk the crate, emitting modules for all modules found
         visit::visit_file(&mut gen, &ast);
+        match gen.error {
+            Some(e) => Err(e),
+            None => Ok(gen.cpp_files),
+        }
+    }
+
+    fn dst(&self) -> PathBuf {
+        self.out_dir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(env::var_os("OUT_DIR").unwrap()))
+    }
+
+    fn generator<'b>(
+        &'b self,
+        out_dir: &'b PathBuf,
+        krate: &'b Path,
+        netlist: Option<(&'b str, &'b [VerilogPort])>,
+    ) -> Generator<'b> {
+        Generator {
+            out_dir,
+            krate,
+            netlist,
+            found_module: false,
+            dry_run: false,
+            context: self
+                .context
+                .unwrap_or_else(|| match env::var("DEP_VERILATED_VERSION") {
+                    Ok(version) => version
+                        .parse::<Version>()
+                        .is_ok_and(|v| has_verilated_context((v.major, v.minor))),
+                    Err(..) => verilator_version().is_some_and(has_verilated_context),
+                }),
+            prefix: self.prefix.as_deref(),
+            // `verilated` only has its `vcd` module with the `vcd` feature,
+            // even when the runtime it links includes the VCD writer.
+            trace_vcd: self.trace_vcd.unwrap_or_else(|| {
+                env::var("DEP_VERILATED_FEATURES").map_or(true, |features| {
+                    features.split_whitespace().any(|f| f == "vcd")
+                })
+            }),
+            trace_fst: self.trace_fst,
+            checked_ports: self.checked_ports,
+            cpp_files: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Sort the ports of the netlist into the clock, the reset and the
+    /// accessors of the generated module.
+    fn bindings_ports(&self, verilog: &[VerilogPort]) -> Result<Ports, ModuleError> {
+        let mut ports = Ports {
+            clock: None,
+            reset: None,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            inouts: Vec::new(),
+        };
+        for v in verilog {
+            let (ty, width) = width_ty(v.width, v.signed)
+                .map_err(|e| ModuleError::Port(format!("`{}`: {}", v.name, e)))?;
+            let port = Port {
+                name: v.name.clone(),
+                ty,
+                width,
+                line: None,
+            };
+            let is_clock = self.clock.as_ref() == Some(&v.name);
+            let is_reset = self.reset.as_ref() == Some(&v.name);
+            if (is_clock || is_reset) && (v.direction != Direction::Input || v.width != 1) {
+                return Err(ModuleError::Port(format!(
+                    "clock and reset must be single bit inputs, `{}` is not",
+                    v.name
+                )));
+            }
+            match v.direction {
+                _ if is_clock => ports.clock = Some(port),
+                _ if is_reset => ports.reset = Some(port),
+                Direction::Input => ports.inputs.push(port),
+                Direction::Output => ports.outputs.push(port),
+                Direction::InOut => ports.inouts.push(port),
+            }
+        }
+        for (name, port) in &[(&self.clock, &ports.clock), (&self.reset, &ports.reset)] {
+            if let (Some(name), None) = (name, port) {
+                return Err(ModuleError::Port(format!(
+                    "the top module has no port `{}`",
+                    name
+                )));
+            }
+        }
+        Ok(ports)
     }
 }
 
@@ -108,16 +451,20 @@ impl Default for ModuleGenerator {
         ModuleGenerator {
             out_dir: None,
             target: None,
+            prefix: None,
+            trace_vcd: None,
+            trace_fst: false,
+            checked_ports: false,
+            context: None,
+            clock: None,
+            reset: None,
+            netlist: None,
         }
     }
 }
 
 fn check_name(attr: &Attribute, name: &str) -> bool {
-    if let Some(meta) = attr.interpret_meta() {
-        meta.name() == name
-    } else {
-        false
-    }
+    attr.path.is_ident(name)
 }
 
 fn is_public(vis: &Visibility) -> bool {
@@ -127,15 +474,26 @@ fn is_public(vis: &Visibility) -> bool {
     }
 }
 
+fn read_netlist(xml: &Path, top: &str) -> Result<Vec<VerilogPort>, ModuleError> {
+    let content = fs::read_to_string(xml)?;
+    xml::parse(&content, top).map_err(|e| ModuleError::Netlist(format!("{}: {}", xml.display(), e)))
+}
+
+/// The C++ file of the shim of the model `c_ty`.
+fn cpp_file(out_dir: &Path, c_ty: &str) -> PathBuf {
+    out_dir.join(format!("{}.cpp", c_ty))
+}
+
 struct StructFinder {
     structs: FnvHashSet<String>,
 }
 
 impl<'ast> Visit<'ast> for StructFinder {
     fn visit_item_struct(&mut self, i: &'ast ItemStruct) {
-        let any_module = i.attrs.iter().any(|attr| {
-            attr.style == AttrStyle::Outer && !attr.is_sugared_doc && check_name(attr, "module")
-        });
+        let any_module = i
+            .attrs
+            .iter()
+            .any(|attr| attr.style == AttrStyle::Outer && check_name(attr, "module"));
         if any_module {
             self.structs.insert(i.ident.to_string());
         }
@@ -144,7 +502,77 @@ impl<'ast> Visit<'ast> for StructFinder {
 
 struct Port {
     name: String,
+    /// The Rust type of the port's accessors.
     ty: String,
+    /// The width of the Verilog port.
+    width: usize,
+    /// The line of the field declaring the port, if any.
+    line: Option<usize>,
+}
+
+impl Port {
+    fn signed(&self) -> bool {
+        self.ty.starts_with('i')
+    }
+
+    /// The unsigned type the port is passed through the FFI as.
+    fn raw_ty(&self) -> String {
+        self.ty.replacen('i', "u", 1)
+    }
+
+    /// The number of bits of the Rust type of a port up to 64 bits wide.
+    fn bits(&self) -> usize {
+        self.ty[1..].parse().expect("not a scalar port")
+    }
+
+    /// The mask of the bits of a port up to 64 bits wide.
+    fn mask(&self) -> u64 {
+        u64::MAX >> (64 - self.width)
+    }
+
+    /// The statement checking that `v` fits in the width of the port.
+    fn check(&self) -> Option<String> {
+        let (fits, value) = match (self.words(), self.width % 32) {
+            (Some(words), bits) if bits != 0 => {
+                (format!("v[{}] >> {} == 0", words - 1, bits), "{:x?}")
+            }
+            (Some(..), _) => return None,
+            (None, _) if self.width == self.bits() => return None,
+            (None, _) if self.signed() => {
+                let max = (1i64 << (self.width - 1)) - 1;
+                (format!("({}..={}).contains(&v)", -max - 1, max), "{}")
+            }
+            (None, _) => (format!("v <= {:#x}", self.mask()), "{:#x}"),
+        };
+        Some(format!(
+            r#"assert!({fits}, "value {value} does not fit in the {width} bit port `{port}`", v);"#,
+            fits = fits,
+            value = value,
+            width = self.width,
+            port = self.name
+        ))
+    }
+
+    /// The number of 32-bit words of a port wider than 64 bits, which
+    /// verilator stores as a `WData` array.
+    fn words(&self) -> Option<usize> {
+        if self.width > 64 {
+            Some(self.width.div_ceil(32))
+        } else {
+            None
+        }
+    }
+
+    /// The statement clearing the unused bits of the most significant word
+    /// of the wide port `var`, if it has any.
+    fn mask_top_word(&self, var: &str) -> String {
+        match (self.words(), self.width % 32) {
+            (Some(words), bits) if bits != 0 => {
+                format!("\n    {}[{}] &= {:#x}U;", var, words - 1, (1u32 << bits) - 1)
+            }
+            _ => String::new(),
+        }
+    }
 }
 
 struct Ports {
@@ -158,7 +586,20 @@ struct Ports {
 struct Generator<'b> {
     out_dir: &'b PathBuf,
     krate: &'b Path,
+    /// The name and ports of the verilated top module, to check structs
+    /// against.
+    netlist: Option<(&'b str, &'b [VerilogPort])>,
     found_module: bool,
+    /// Only list the C++ files of the shims instead of writing them.
+    dry_run: bool,
+    context: bool,
+    prefix: Option<&'b str>,
+    trace_vcd: bool,
+    trace_fst: bool,
+    checked_ports: bool,
+    cpp_files: Vec<PathBuf>,
+    /// The first error found walking the crate.
+    error: Option<ModuleError>,
 }
 
 impl<'b> Generator<'b> {
@@ -167,20 +608,88 @@ impl<'b> Generator<'b> {
         assert!(generics.where_clause.is_none());
     }
 
-    fn gen_module(&mut self, rs_ty: &str, c_ty: &str, s: &ItemStruct) {
-        let rs_file = self.out_dir.join(format!("{}.rs", rs_ty));
-        let mut rs_out = BufWriter::new(t!(File::create(&rs_file)));
+    /// Check every `#[port]` of `rs_ty` against the netlist, returning a
+    /// warning for each input of the top module without a field.
+    fn check_ports(&self, rs_ty: &str, ports: &Ports) -> Result<Vec<String>, ModuleError> {
+        let (top, verilog) = match self.netlist {
+            Some(netlist) => netlist,
+            None => return Ok(Vec::new()),
+        };
+        let declared: Vec<_> = ports
+            .clock
+            .iter()
+            .chain(&ports.reset)
+            .chain(&ports.inputs)
+            .map(|p| (p, Direction::Input))
+            .chain(ports.outputs.iter().map(|p| (p, Direction::Output)))
+            .chain(ports.inouts.iter().map(|p| (p, Direction::InOut)))
+            .collect();
+
+        let mut errors = Vec::new();
+        for &(port, direction) in &declared {
+            let error = match verilog.iter().find(|v| v.name == port.name) {
+                None => format!("`{}` is not a port of `{}`", port.name, top),
+                Some(v) if v.direction != direction => format!(
+                    "`{}` is declared an {}, but is an {} of `{}`",
+                    port.name, direction, v.direction, top
+                ),
+                Some(v) if v.width != port.width => format!(
+                    "`{}` is declared {} bits wide, but is {} bits wide in `{}`",
+                    port.name, port.width, v.width, top
+                ),
+                Some(..) => continue,
+            };
+            match port.line {
+                Some(line) => errors.push(format!("{}:{}: {}", self.krate.display(), line, error)),
+                None => errors.push(format!("{}: {}", self.krate.display(), error)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ModuleError::PortMismatch {
+                rs_ty: rs_ty.to_string(),
+                top: top.to_string(),
+                errors,
+            });
+        }
+
+        let warnings = verilog
+            .iter()
+            .filter(|v| v.direction != Direction::Output)
+            .filter(|v| !declared.iter().any(|(p, _)| p.name == v.name))
+            .map(|v| {
+                format!(
+                    "{} `{}` of `{}` has no field in `{}`",
+                    v.direction, v.name, top, rs_ty
+                )
+            })
+            .collect();
+        Ok(warnings)
+    }
 
-        let cpp_file = self.out_dir.join(format!("{}.cpp", c_ty));
-        let mut cpp_out = BufWriter::new(t!(File::create(&cpp_file)));
+    /// The error of an invalid `#[port]` field, at the line of `e`'s span.
+    fn field_error(&self, e: syn::Error) -> ModuleError {
+        let line = e.span().start().line;
+        ModuleError::Field(format!("{}:{}: {}", self.krate.display(), line, e))
+    }
+
+    /// Write the shim of the model `c_ty` as `rs_ty`, returning its C++
+    /// file.
+    fn gen_module(
+        &mut self,
+        rs_ty: &str,
+        c_ty: &str,
+        ports: Ports,
+    ) -> Result<PathBuf, ModuleError> {
+        let rs_file = self.out_dir.join(format!("{}.rs", rs_ty));
+        let mut rs_out = BufWriter::new(File::create(&rs_file)?);
 
-        let ports = extract_ports(&s.fields);
+        let cpp_file = cpp_file(self.out_dir, c_ty);
+        let mut cpp_out = BufWriter::new(File::create(&cpp_file)?);
+        self.cpp_files.push(cpp_file.clone());
 
         t!(writeln!(
             rs_out,
-            r#"use std::path::Path;
-
-mod ffi {{
+            r#"mod ffi {{
     #[allow(non_camel_case_types)]
     pub enum {c_ty} {{}}
 
@@ -188,37 +697,97 @@ mod ffi {{
         pub fn {c_ty}_new() -> *mut {c_ty};
         pub fn {c_ty}_delete({c_ty}: *mut {c_ty});
         pub fn {c_ty}_eval({c_ty}: *mut {c_ty});
-        pub fn {c_ty}_trace({c_ty}: *mut {c_ty}, vcd: *mut ::verilated::vcd::VcdC, levels: ::std::os::raw::c_int);
         pub fn {c_ty}_final({c_ty}: *mut {c_ty});"#,
             c_ty = c_ty
         ));
 
+        if self.trace_vcd {
+            t!(writeln!(
+                rs_out,
+                r#"        pub fn {c_ty}_trace({c_ty}: *mut {c_ty}, vcd: *mut ::verilated::vcd::VcdC, levels: ::std::os::raw::c_int) -> ::std::os::raw::c_int;"#,
+                c_ty = c_ty
+            ));
+        }
+
+        if self.trace_fst {
+            t!(writeln!(
+                rs_out,
+                r#"        pub fn {c_ty}_trace_fst({c_ty}: *mut {c_ty}, fst: *mut ::verilated::fst::FstC, levels: ::std::os::raw::c_int) -> ::std::os::raw::c_int;"#,
+                c_ty = c_ty
+            ));
+        }
+
+        if self.context {
+            t!(writeln!(
+                rs_out,
+                r#"        pub fn {c_ty}_new_with_context(context: *mut ::verilated::context::ContextC) -> *mut {c_ty};"#,
+                c_ty = c_ty
+            ));
+        }
+
         t!(writeln!(
             cpp_out,
             r#"#include <V{c_ty}.h>
 
-extern "C" {{
-  // CONSTRUCTORS
-  V{c_ty}*
-  {c_ty}_new() {{
-    V{c_ty}*ptr = new V{c_ty}();"#,
+// Verilator 4 models are traced to either VCD or FST, Verilator 5 models to
+// any of the formats they were verilated with.
+#define {c_ty}_TRACE_VCD (VM_TRACE_VCD || (VM_TRACE && !VM_TRACE_FST))
+
+class VerilatedVcdC;
+class VerilatedFstC;
+#if {c_ty}_TRACE_VCD
+#include <verilated_vcd_c.h>
+#endif
+#if VM_TRACE_FST
+#include <verilated_fst_c.h>
+#endif
+
+static void
+{c_ty}_init(V{c_ty}* ptr) {{"#,
             c_ty = c_ty
         ));
 
         if let Some(clock) = &ports.clock {
-            t!(writeln!(cpp_out, "    ptr->{clk} = 0;", clk = clock.name));
+            t!(writeln!(cpp_out, "  ptr->{clk} = 0;", clk = clock.name));
         }
 
         if let Some(reset) = &ports.reset {
-            t!(writeln!(cpp_out, "    ptr->{rst} = 0;", rst = reset.name));
+            t!(writeln!(cpp_out, "  ptr->{rst} = 0;", rst = reset.name));
         }
 
         t!(writeln!(
             cpp_out,
-            r#"    return ptr;
+            r#"}}
+
+extern "C" {{
+  // CONSTRUCTORS
+  V{c_ty}*
+  {c_ty}_new() {{
+    V{c_ty}*ptr = new V{c_ty}();
+    {c_ty}_init(ptr);
+    return ptr;
   }}
+"#,
+            c_ty = c_ty
+        ));
 
-  void
+        if self.context {
+            t!(writeln!(
+                cpp_out,
+                r#"  V{c_ty}*
+  {c_ty}_new_with_context(VerilatedContext* __contextp) {{
+    V{c_ty}*ptr = new V{c_ty}(__contextp);
+    {c_ty}_init(ptr);
+    return ptr;
+  }}
+"#,
+                c_ty = c_ty
+            ));
+        }
+
+        t!(writeln!(
+            cpp_out,
+            r#"  void
   {c_ty}_delete(V{c_ty}* __ptr) {{
     delete __ptr;
   }}
@@ -229,9 +798,24 @@ extern "C" {{
     __ptr->eval();
   }}
 
-  void
+  int
   {c_ty}_trace(V{c_ty}* __ptr, VerilatedVcdC* __tfp, int __levels) {{
+#if {c_ty}_TRACE_VCD
     __ptr->trace(__tfp, __levels);
+    return 1;
+#else
+    return 0;
+#endif
+  }}
+
+  int
+  {c_ty}_trace_fst(V{c_ty}* __ptr, VerilatedFstC* __tfp, int __levels) {{
+#if VM_TRACE_FST
+    __ptr->trace(__tfp, __levels);
+    return 1;
+#else
+    return 0;
+#endif
   }}
 
   void
@@ -261,20 +845,58 @@ extern "C" {{
 
         t!(writeln!(
             rs_out,
-            r#"pub struct {rs_ty}(*mut ffi::{c_ty}, Option<::verilated::vcd::Vcd>);
+            r#"pub struct {rs_ty} {{
+    ptr: *mut ffi::{c_ty},
+    tracing: bool,
+    trace_window: Option<(u64, u64)>,"#,
+            rs_ty = rs_ty,
+            c_ty = c_ty
+        ));
+
+        if self.trace_vcd {
+            t!(writeln!(rs_out, r#"    vcd: Option<::verilated::vcd::Vcd>,"#));
+        }
+
+        if self.trace_fst {
+            t!(writeln!(rs_out, r#"    fst: Option<::verilated::fst::Fst>,"#));
+        }
+
+        if self.context {
+            t!(writeln!(
+                rs_out,
+                r#"    context: Option<::std::rc::Rc<::verilated::context::Context>>,"#
+            ));
+        }
+
+        let fields = |context: &str| {
+            let mut fields = "ptr, tracing: true, trace_window: None".to_string();
+            if self.trace_vcd {
+                fields += ", vcd: None";
+            }
+            if self.trace_fst {
+                fields += ", fst: None";
+            }
+            if self.context {
+                fields += &format!(", context: {}", context);
+            }
+            fields
+        };
+        t!(writeln!(
+            rs_out,
+            r#"}}
 
 impl Default for {rs_ty} {{
     fn default() -> Self {{
         let ptr = unsafe {{ ffi::{c_ty}_new() }};
         assert!(!ptr.is_null());
-        {rs_ty}(ptr, None)
+        {rs_ty} {{ {fields} }}
     }}
 }}
 
 impl Drop for {rs_ty} {{
     fn drop(&mut self) {{
         unsafe {{
-            ffi::{c_ty}_delete(self.0);
+            ffi::{c_ty}_delete(self.ptr);
         }}
     }}
 }}
@@ -282,50 +904,42 @@ impl Drop for {rs_ty} {{
 #[allow(dead_code, non_snake_case)]
 impl {rs_ty} {{"#,
             c_ty = c_ty,
-            rs_ty = rs_ty
+            rs_ty = rs_ty,
+            fields = fields("None")
         ));
 
-        for input in &ports.inputs {
+        if self.context {
             t!(writeln!(
                 rs_out,
-                r#"    pub fn set_{input}(&mut self, v: {ty}) {{
-        unsafe {{ ffi::{c_ty}_set_{input}(self.0, v); }}
+                r#"    /// Construct the model against `context` instead of the default
+    /// global context.
+    pub fn with_context(context: ::std::rc::Rc<::verilated::context::Context>) -> Self {{
+        let ptr = unsafe {{ ffi::{c_ty}_new_with_context(context.0) }};
+        assert!(!ptr.is_null());
+        {rs_ty} {{ {fields} }}
+    }}
+
+    pub fn context(&self) -> Option<&::std::rc::Rc<::verilated::context::Context>> {{
+        self.context.as_ref()
     }}
 "#,
                 c_ty = c_ty,
-                input = input.name,
-                ty = &input.ty
+                rs_ty = rs_ty,
+                fields = fields("Some(context)")
             ));
         }
 
+        for input in &ports.inputs {
+            write_rs_setter(&mut rs_out, c_ty, input, self.checked_ports);
+        }
+
         for output in &ports.outputs {
-            t!(writeln!(
-                rs_out,
-                r#"    pub fn {output}(&self) -> {ty} {{
-        unsafe {{ ffi::{c_ty}_get_{output}(self.0) }}
-    }}
-"#,
-                c_ty = c_ty,
-                output = output.name,
-                ty = &output.ty
-            ));
+            write_rs_getter(&mut rs_out, c_ty, output);
         }
 
         for inout in &ports.inouts {
-            t!(writeln!(
-                rs_out,
-                r#"    pub fn set_{inout}(&mut self, v: {ty}) {{
-        unsafe {{ ffi::{c_ty}_set_{inout}(self.0, v); }}
-    }}
-
-    pub fn {inout}(&self) -> {ty} {{
-        unsafe {{ ffi::{c_ty}_get_{inout}(self.0) }}
-    }}
-"#,
-                c_ty = c_ty,
-                inout = inout.name,
-                ty = &inout.ty
-            ));
+            write_rs_setter(&mut rs_out, c_ty, inout, self.checked_ports);
+            write_rs_getter(&mut rs_out, c_ty, inout);
         }
 
         t!(writeln!(
@@ -333,13 +947,13 @@ impl {rs_ty} {{"#,
             r#"
     pub fn eval(&mut self) {{
         unsafe {{
-            ffi::{c_ty}_eval(self.0);
+            ffi::{c_ty}_eval(self.ptr);
         }}
     }}
 
     pub fn finish(&mut self) {{
         unsafe {{
-            ffi::{c_ty}_final(self.0);
+            ffi::{c_ty}_final(self.ptr);
         }}
     }}
 "#,
@@ -347,27 +961,144 @@ impl {rs_ty} {{"#,
         ));
 
         // Tracing API
-        t!(writeln!(
-            rs_out,
-            r#"    pub fn open_trace<P: AsRef<Path>>(&mut self, path: P, levels: i32) -> std::io::Result<()> {{
+        if self.context {
+            t!(writeln!(
+                rs_out,
+                r#"    fn trace_ever_on(&self) {{
+        match self.context {{
+            Some(ref context) => context.trace_ever_on(true),
+            None => ::verilated::trace_ever_on(true),
+        }}
+    }}
+"#
+            ));
+        } else {
+            t!(writeln!(
+                rs_out,
+                r#"    fn trace_ever_on(&self) {{
         ::verilated::trace_ever_on(true);
+    }}
+"#
+            ));
+        }
+
+        if self.trace_vcd {
+            t!(writeln!(
+                rs_out,
+                r#"    pub fn open_trace<P: AsRef<::std::path::Path>>(&mut self, path: P, levels: i32) -> std::io::Result<()> {{
+        self.trace_ever_on();
         let mut vcd = ::verilated::vcd::Vcd::default();
-        unsafe {{
-            ffi::{c_ty}_trace(self.0, vcd.0, levels);
+        if unsafe {{ ffi::{c_ty}_trace(self.ptr, vcd.0, levels) }} == 0 {{
+            return Err(::std::io::Error::new(
+                ::std::io::ErrorKind::Other,
+                "model was not verilated with VCD tracing",
+            ));
         }}
         vcd.open(path)?;
-        self.1 = Some(vcd);
+        self.vcd = Some(vcd);
         Ok(())
     }}
+"#,
+                c_ty = c_ty
+            ));
+        }
 
-    pub fn trace_at(&mut self, nanos: ::std::time::Duration) {{
-        if let Some(ref mut vcd) = self.1 {{
-            let timeui = nanos.as_secs() * 1_000_000_000 + u64::from(nanos.subsec_nanos());
-            vcd.dump(timeui);
+        if self.trace_fst {
+            t!(writeln!(
+                rs_out,
+                r#"    pub fn open_trace_fst<P: AsRef<::std::path::Path>>(&mut self, path: P, levels: i32) -> std::io::Result<()> {{
+        self.trace_ever_on();
+        let mut fst = ::verilated::fst::Fst::default();
+        if unsafe {{ ffi::{c_ty}_trace_fst(self.ptr, fst.0, levels) }} == 0 {{
+            return Err(::std::io::Error::new(
+                ::std::io::ErrorKind::Other,
+                "model was not verilated with FST tracing",
+            ));
         }}
+        fst.open(path)?;
+        self.fst = Some(fst);
+        Ok(())
     }}
 "#,
-            c_ty = c_ty
+                c_ty = c_ty
+            ));
+        }
+
+        t!(writeln!(
+            rs_out,
+            r#"    /// Pause or resume dumping trace data in `trace_at`.
+    pub fn set_tracing(&mut self, on: bool) {{
+        self.tracing = on;
+        if !on {{
+            self.trace_flush();
+        }}
+    }}
+
+    /// Only dump trace data in `trace_at` for times in `start..end`.
+    pub fn trace_window(&mut self, start: ::std::time::Duration, end: ::std::time::Duration) {{
+        let nanos = |t: ::std::time::Duration| t.as_secs() * 1_000_000_000 + u64::from(t.subsec_nanos());
+        self.trace_window = Some((nanos(start), nanos(end)));
+    }}
+
+    pub fn trace_at(&mut self, nanos: ::std::time::Duration) {{
+        let timeui = nanos.as_secs() * 1_000_000_000 + u64::from(nanos.subsec_nanos());
+        if !self.tracing {{
+            return;
+        }}
+        if let Some((start, end)) = self.trace_window {{
+            if timeui < start || timeui >= end {{
+                return;
+            }}
+        }}"#
+        ));
+
+        if self.trace_vcd {
+            t!(writeln!(
+                rs_out,
+                r#"        if let Some(ref mut vcd) = self.vcd {{
+            vcd.dump(timeui);
+        }}"#
+            ));
+        }
+
+        if self.trace_fst {
+            t!(writeln!(
+                rs_out,
+                r#"        if let Some(ref mut fst) = self.fst {{
+            fst.dump(timeui);
+        }}"#
+            ));
+        }
+
+        t!(writeln!(
+            rs_out,
+            r#"    }}
+
+    pub fn trace_flush(&mut self) {{"#
+        ));
+
+        if self.trace_vcd {
+            t!(writeln!(
+                rs_out,
+                r#"        if let Some(ref mut vcd) = self.vcd {{
+            vcd.flush();
+        }}"#
+            ));
+        }
+
+        if self.trace_fst {
+            t!(writeln!(
+                rs_out,
+                r#"        if let Some(ref mut fst) = self.fst {{
+            fst.flush();
+        }}"#
+            ));
+        }
+
+        t!(writeln!(
+            rs_out,
+            r#"    }}
+"#
         ));
 
         if let Some(clock) = ports.clock {
@@ -375,7 +1106,7 @@ impl {rs_ty} {{"#,
                 rs_out,
                 r#"    pub fn clock_toggle(&mut self) {{
         unsafe {{
-            ffi::{c_ty}_{clk}_toggle(self.0);
+            ffi::{c_ty}_{clk}_toggle(self.ptr);
         }}
     }}
 "#,
@@ -397,7 +1128,7 @@ impl {rs_ty} {{"#,
                 rs_out,
                 r#"    pub fn reset_toggle(&mut self) {{
         unsafe {{
-            ffi::{c_ty}_{rst}_toggle(self.0);
+            ffi::{c_ty}_{rst}_toggle(self.ptr);
         }}
     }}
 "#,
@@ -419,6 +1150,9 @@ fn reset_down(&mut self) {{
         }
 
         t!(writeln!(rs_out, r#"}}"#));
+        rs_out.flush()?;
+        cpp_out.flush()?;
+        Ok(cpp_file)
     }
 }
 
@@ -431,16 +1165,44 @@ impl<'ast, 'b> Visit<'ast> for Generator<'b> {
         for attr in &i.attrs {
             let acc = find_module_attrs(attr);
             if !acc.is_empty() {
-                let rs_ty = i.ident.to_string();
-                let c_ty = &acc[0];
-                self.gen_module(&rs_ty, &c_ty, i);
-
-                if !self.found_module {
+                if !self.found_module && !self.dry_run {
                     if let Some(path) = self.krate.to_str() {
                         println!("cargo:rerun-if-changed={}", path);
                     }
                 }
                 self.found_module = true;
+
+                let rs_ty = i.ident.to_string();
+                let c_ty = &acc[0];
+                if self.prefix.is_some_and(|p| p != format!("V{}", c_ty)) {
+                    continue;
+                }
+                if self.dry_run {
+                    self.cpp_files.push(cpp_file(self.out_dir, c_ty));
+                    continue;
+                }
+                let ports = match extract_ports(&i.fields) {
+                    Ok(ports) => ports,
+                    Err(e) => {
+                        let e = self.field_error(e);
+                        self.error.get_or_insert(e);
+                        continue;
+                    }
+                };
+                match self.check_ports(&rs_ty, &ports) {
+                    Ok(warnings) => {
+                        for warning in warnings {
+                            println!("cargo:warning={}", warning);
+                        }
+                    }
+                    Err(e) => {
+                        self.error.get_or_insert(e);
+                        continue;
+                    }
+                }
+                if let Err(e) = self.gen_module(&rs_ty, c_ty, ports) {
+                    self.error.get_or_insert(e);
+                }
             }
         }
     }
@@ -448,10 +1210,10 @@ impl<'ast, 'b> Visit<'ast> for Generator<'b> {
 
 fn find_module_attrs(attr: &Attribute) -> Vec<String> {
     let mut acc = Vec::new();
-    if let Some(meta) = attr.interpret_meta() {
+    if let Ok(meta) = attr.parse_meta() {
         match meta {
-            Meta::List(ref list) if meta.name() == "module" => for item in &list.nested {
-                if let NestedMeta::Meta(Meta::Word(ref ident)) = item {
+            Meta::List(ref list) if list.path.is_ident("module") => for item in &list.nested {
+                if let Some(ident) = nested_word(item) {
                     acc.push(ident.to_string())
                 }
             },
@@ -470,85 +1232,165 @@ enum PortAttr {
     InOut,
 }
 
-type NestedMetaList = Punctuated<NestedMeta, Comma>;
-fn list_contains_name(list: &NestedMetaList, name: &str) -> bool {
-    list.iter().any(|meta| match meta {
-        NestedMeta::Meta(Meta::Word(ref ident)) if ident == name => true,
-        _ => false,
-    })
+/// The options of a `#[port(...)]` attribute besides the kind of port.
+struct PortOptions {
+    /// `width = N`, the width of the Verilog port when narrower than the
+    /// Rust type.
+    width: Option<usize>,
+    /// `signed`, for ports declared `[bool; N]`.
+    signed: bool,
 }
 
-fn find_port_attr(attrs: &[Attribute]) -> PortAttr {
-    attrs.iter().fold(PortAttr::None, |pa, attr| {
-        let meta = match attr.interpret_meta() {
-            Some(meta) => meta,
-            None => return pa,
+fn find_port_attr(attrs: &[Attribute]) -> syn::Result<(PortAttr, PortOptions)> {
+    let mut options = PortOptions {
+        width: None,
+        signed: false,
+    };
+    for attr in attrs {
+        if !attr.path.is_ident("port") {
+            continue;
+        }
+        let items = match attr.parse_meta()? {
+            syn::Meta::List(items) => items.nested,
+            meta => return Err(syn::Error::new_spanned(meta, "expected one argument")),
         };
-        match meta {
-            syn::Meta::List(ref items) => {
-                if items.ident != "port" {
-                    return pa;
-                }
 
-                if items.nested.len() != 1 {
-                    panic!("expected one argument");
-                // PortAttr::None
-                } else if list_contains_name(&items.nested, "clock") {
-                    PortAttr::Clock
-                } else if list_contains_name(&items.nested, "reset") {
-                    PortAttr::Reset
-                } else if list_contains_name(&items.nested, "input") {
-                    PortAttr::Input
-                } else if list_contains_name(&items.nested, "output") {
-                    PortAttr::Output
-                } else if list_contains_name(&items.nested, "inout") {
-                    PortAttr::InOut
-                } else {
-                    panic!("invalid argument");
-                    // PortAttr::None
+        let mut kind = PortAttr::None;
+        for item in &items {
+            let next = match *item {
+                _ if is_word(item, "clock") => PortAttr::Clock,
+                _ if is_word(item, "reset") => PortAttr::Reset,
+                _ if is_word(item, "input") => PortAttr::Input,
+                _ if is_word(item, "output") => PortAttr::Output,
+                _ if is_word(item, "inout") => PortAttr::InOut,
+                _ if is_word(item, "signed") => {
+                    options.signed = true;
+                    continue;
                 }
+                NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("width") => {
+                    match nv.lit {
+                        syn::Lit::Int(ref w) => options.width = Some(w.base10_parse()?),
+                        ref lit => {
+                            return Err(syn::Error::new_spanned(lit, "expected an integer width"))
+                        }
+                    }
+                    continue;
+                }
+                _ => return Err(syn::Error::new_spanned(item, "invalid argument")),
+            };
+            if let PortAttr::None = kind {
+                kind = next;
+            } else {
+                return Err(syn::Error::new_spanned(
+                    item,
+                    "expected one of clock, reset, input, output or inout",
+                ));
             }
-            syn::Meta::Word(..) => {
-                panic!("expected one argument");
-                // PortAttr::None
-            }
-            _ => pa,
         }
-    })
+        if let PortAttr::None = kind {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "expected one of clock, reset, input, output or inout",
+            ));
+        }
+        return Ok((kind, options));
+    }
+    Ok((PortAttr::None, options))
+}
+
+/// The identifier of a bare word in an attribute, such as `input` in
+/// `#[port(input)]`.
+fn nested_word(item: &NestedMeta) -> Option<&Ident> {
+    match *item {
+        NestedMeta::Meta(Meta::Path(ref path)) => path.get_ident(),
+        _ => None,
+    }
+}
+
+fn is_word(item: &NestedMeta, word: &str) -> bool {
+    nested_word(item).is_some_and(|ident| ident == word)
 }
 
-fn expr2width(e: &syn::Expr) -> usize {
+fn expr2width(e: &syn::Expr) -> syn::Result<usize> {
     match e {
-        syn::Expr::Lit(ref l) => match l.lit {
-            syn::Lit::Int(ref a) => a.value() as usize,
-            _ => panic!("unknown literal: {:?}", l),
-        },
-        _ => panic!("unknown expr: {:?}", e),
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(ref a),
+            ..
+        }) => a.base10_parse(),
+        _ => Err(syn::Error::new_spanned(
+            e,
+            "expected an integer array length",
+        )),
     }
 }
 
-fn ty2name(ty: &syn::Type) -> String {
-    match ty {
+/// The Rust type of the accessors of a port declared as `ty`, and the
+/// width of the Verilog port.
+///
+/// `bool` and `[bool; N]` are accessed as the smallest unsigned integer
+/// holding the port, or signed with `signed`. Integer types are accessed as
+/// themselves, and are as wide as the port unless narrowed with `width`.
+fn port_ty(ty: &syn::Type, options: &PortOptions) -> syn::Result<(String, usize)> {
+    let error = |message: String| syn::Error::new_spanned(ty, message);
+    let (width, signed) = match ty {
         syn::Type::Path(syn::TypePath { ref path, .. }) => {
-            let last = path.segments.last().unwrap();
-            if last.into_value().ident == "bool" {
-                "u8".to_string()
-            } else {
-                panic!("only support bool");
+            let ident = path.segments.last().unwrap().ident.to_string();
+            match ident.as_str() {
+                "bool" => (1, false),
+                "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64" => {
+                    let bits = ident[1..].parse().unwrap();
+                    let width = options.width.unwrap_or(bits);
+                    if width == 0 || width > bits {
+                        return Err(error(format!(
+                            "port of width {} does not fit in `{}`",
+                            width, ident
+                        )));
+                    }
+                    if options.signed && ident.starts_with('u') {
+                        return Err(error(format!(
+                            "signed ports must be declared `i{}`, not `{}`",
+                            bits, ident
+                        )));
+                    }
+                    return Ok((ident, width));
+                }
+                _ => return Err(error(format!("unsupported port type `{}`", ident))),
             }
         }
         syn::Type::Array(arr) => {
-            assert!(ty2name(&*arr.elem) == "u8");
-            match expr2width(&arr.len) {
-                0..=8 => "u8".to_string(),
-                9..=16 => "u16".to_string(),
-                17..=32 => "u32".to_string(),
-                33..=64 => "u64".to_string(),
-                _ => unreachable!(),
+            match *arr.elem {
+                syn::Type::Path(syn::TypePath { ref path, .. })
+                    if path.segments.last().unwrap().ident == "bool" => {}
+                _ => return Err(error("only arrays of bool are supported".to_string())),
             }
+            (expr2width(&arr.len)?, false)
         }
-        _ => panic!("unknown ty {:?}", ty),
+        _ => return Err(error("unsupported port type".to_string())),
+    };
+    if options.width.is_some_and(|w| w != width) {
+        return Err(error("`width` only narrows integer ports".to_string()));
     }
+    width_ty(width, signed || options.signed).map_err(error)
+}
+
+/// The Rust type of the accessors of a Verilog port `width` bits wide: the
+/// smallest integer holding the port, or `u32` words above 64 bits.
+fn width_ty(width: usize, signed: bool) -> Result<(String, usize), String> {
+    let name = match width {
+        0 => return Err("ports must be at least one bit wide".to_string()),
+        1..=8 => "u8".to_string(),
+        9..=16 => "u16".to_string(),
+        17..=32 => "u32".to_string(),
+        33..=64 => "u64".to_string(),
+        width => format!("[u32; {}]", width.div_ceil(32)),
+    };
+    if signed {
+        if width > 64 {
+            return Err("signed ports wider than 64 bits are not supported".to_string());
+        }
+        return Ok((name.replacen('u', "i", 1), width));
+    }
+    Ok((name, width))
 }
 
 fn rust2ffi(ty: &str) -> String {
@@ -571,8 +1413,8 @@ fn rust2ver(ty: &str) -> String {
     }
 }
 
-fn extract_ports(fields: &Fields) -> Ports {
-    let ports = Ports {
+fn extract_ports(fields: &Fields) -> syn::Result<Ports> {
+    let mut ports = Ports {
         clock: None,
         reset: None,
         inputs: Vec::new(),
@@ -580,47 +1422,62 @@ fn extract_ports(fields: &Fields) -> Ports {
         inouts: Vec::new(),
     };
 
-    match fields {
-        Fields::Named(ref fields) => fields.named.iter().fold(ports, |mut ports, field| {
-            let port = match field.ident {
-                Some(name) => {
-                    let name = name.to_string();
-                    let ty = ty2name(&field.ty);
-                    Port { name, ty }
-                }
-                None => panic!("no tuple structs in FFI"),
-            };
+    let fields = match fields {
+        Fields::Named(ref fields) => fields,
+        _ => return Err(syn::Error::new_spanned(fields, "no tuple structs in FFI")),
+    };
+    for field in &fields.named {
+        let (name, line) = match field.ident {
+            Some(ref name) => (name.to_string(), name.span().start().line),
+            None => return Err(syn::Error::new_spanned(field, "no tuple structs in FFI")),
+        };
 
-            if is_public(&field.vis) {
-                match find_port_attr(&field.attrs) {
-                    PortAttr::Clock => {
-                        if ports.clock.is_some() {
-                            panic!("only one clock allowed in FFI");
-                        }
-                        ports.clock = Some(port);
-                    }
-                    PortAttr::Reset => {
-                        if ports.reset.is_some() {
-                            panic!("only one reset allowed in FFI");
-                        }
-                        ports.reset = Some(port);
-                    }
-                    PortAttr::Input => {
-                        ports.inputs.push(port);
-                    }
-                    PortAttr::Output => {
-                        ports.outputs.push(port);
-                    }
-                    PortAttr::InOut => {
-                        ports.inouts.push(port);
-                    }
-                    _ => {}
+        if !is_public(&field.vis) {
+            continue;
+        }
+        let (kind, options) = find_port_attr(&field.attrs)?;
+        if let PortAttr::None = kind {
+            continue;
+        }
+        let (ty, width) = port_ty(&field.ty, &options)?;
+        let port = Port {
+            name,
+            ty,
+            width,
+            line: Some(line),
+        };
+        match kind {
+            PortAttr::Clock => {
+                if ports.clock.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "only one clock allowed in FFI",
+                    ));
+                }
+                ports.clock = Some(port);
+            }
+            PortAttr::Reset => {
+                if ports.reset.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "only one reset allowed in FFI",
+                    ));
                 }
+                ports.reset = Some(port);
             }
-            ports
-        }),
-        _ => panic!("no tuple structs in FFI"),
+            PortAttr::Input => {
+                ports.inputs.push(port);
+            }
+            PortAttr::Output => {
+                ports.outputs.push(port);
+            }
+            PortAttr::InOut => {
+                ports.inouts.push(port);
+            }
+            _ => {}
+        }
     }
+    Ok(ports)
 }
 
 fn write_clock<W>(rs_out: &mut W, cpp_out: &mut W, c_ty: &str, clock: &Option<Port>)
@@ -678,84 +1535,479 @@ where
     W: Write,
 {
     for input in inputs {
+        write_setter(rs_out, cpp_out, c_ty, input);
+    }
+}
+
+fn write_outputs<W>(rs_out: &mut W, cpp_out: &mut W, c_ty: &str, outputs: &[Port])
+where
+    W: Write,
+{
+    for output in outputs {
+        write_getter(rs_out, cpp_out, c_ty, output);
+    }
+}
+
+fn write_inouts<W>(rs_out: &mut W, cpp_out: &mut W, c_ty: &str, inouts: &[Port])
+where
+    W: Write,
+{
+    for inout in inouts {
+        write_setter(rs_out, cpp_out, c_ty, inout);
+        write_getter(rs_out, cpp_out, c_ty, inout);
+    }
+}
+
+/// Write the FFI declaration and C++ definition of the setter of `port`.
+///
+/// Values are masked to the width of the port, as verilator expects the
+/// unused bits of its fields to be clear. Wide ports are passed as a pointer
+/// to their words, least significant first.
+fn write_setter<W>(rs_out: &mut W, cpp_out: &mut W, c_ty: &str, port: &Port)
+where
+    W: Write,
+{
+    if let Some(words) = port.words() {
         t!(writeln!(
             rs_out,
-            r#"        pub fn {c_ty}_set_{input}({c_ty}: *mut {c_ty}, v: {ffi_ty});"#,
+            r#"        pub fn {c_ty}_set_{port}({c_ty}: *mut {c_ty}, v: *const ::std::os::raw::c_uint);"#,
             c_ty = c_ty,
-            input = input.name,
-            ffi_ty = rust2ffi(&input.ty)
+            port = port.name
         ));
 
         t!(writeln!(
             cpp_out,
             r#"  void
-  {c_ty}_set_{input}(V{c_ty}* __ptr, {v_ty} __v) {{
-    __ptr->{input} = __v;
+  {c_ty}_set_{port}(V{c_ty}* __ptr, const vluint32_t* __v) {{
+    for (int __i = 0; __i < {words}; ++__i) {{
+      __ptr->{port}[__i] = __v[__i];
+    }}{mask}
   }}
 "#,
             c_ty = c_ty,
-            input = input.name,
-            v_ty = rust2ver(&input.ty)
+            port = port.name,
+            words = words,
+            mask = port.mask_top_word(&format!("__ptr->{}", port.name))
+        ));
+    } else {
+        t!(writeln!(
+            rs_out,
+            r#"        pub fn {c_ty}_set_{port}({c_ty}: *mut {c_ty}, v: {ffi_ty});"#,
+            c_ty = c_ty,
+            port = port.name,
+            ffi_ty = rust2ffi(&port.raw_ty())
+        ));
+
+        t!(writeln!(
+            cpp_out,
+            r#"  void
+  {c_ty}_set_{port}(V{c_ty}* __ptr, {v_ty} __v) {{
+    __ptr->{port} = {v};
+  }}
+"#,
+            c_ty = c_ty,
+            port = port.name,
+            v_ty = rust2ver(&port.raw_ty()),
+            v = if port.width < port.bits() {
+                format!("__v & {:#x}ULL", port.mask())
+            } else {
+                "__v".to_string()
+            }
         ));
     }
 }
 
-fn write_outputs<W>(rs_out: &mut W, cpp_out: &mut W, c_ty: &str, outputs: &[Port])
+/// Write the FFI declaration and C++ definition of the getter of `port`.
+///
+/// Wide ports are copied out into the caller's words.
+fn write_getter<W>(rs_out: &mut W, cpp_out: &mut W, c_ty: &str, port: &Port)
 where
     W: Write,
 {
-    for output in outputs {
+    if let Some(words) = port.words() {
+        t!(writeln!(
+            rs_out,
+            r#"        pub fn {c_ty}_get_{port}({c_ty}: *mut {c_ty}, v: *mut ::std::os::raw::c_uint);"#,
+            c_ty = c_ty,
+            port = port.name
+        ));
+
+        t!(writeln!(
+            cpp_out,
+            r#"  void
+  {c_ty}_get_{port}(V{c_ty}* __ptr, vluint32_t* __v) {{
+    for (int __i = 0; __i < {words}; ++__i) {{
+      __v[__i] = __ptr->{port}[__i];
+    }}{mask}
+  }}
+"#,
+            c_ty = c_ty,
+            port = port.name,
+            words = words,
+            mask = port.mask_top_word("__v")
+        ));
+    } else {
         t!(writeln!(
             rs_out,
-            r#"        pub fn {c_ty}_get_{output}({c_ty}: *mut {c_ty}) -> {ffi_ty};"#,
+            r#"        pub fn {c_ty}_get_{port}({c_ty}: *mut {c_ty}) -> {ffi_ty};"#,
             c_ty = c_ty,
-            output = output.name,
-            ffi_ty = rust2ffi(&output.ty)
+            port = port.name,
+            ffi_ty = rust2ffi(&port.raw_ty())
         ));
 
         t!(writeln!(
             cpp_out,
             r#"  {v_ty}
-  {c_ty}_get_{output}(V{c_ty}* __ptr) {{
-    return __ptr->{output};
+  {c_ty}_get_{port}(V{c_ty}* __ptr) {{
+    return __ptr->{port};
   }}
 "#,
             c_ty = c_ty,
-            output = output.name,
-            v_ty = rust2ver(&output.ty)
+            port = port.name,
+            v_ty = rust2ver(&port.raw_ty())
         ));
     }
 }
 
-fn write_inouts<W>(rs_out: &mut W, cpp_out: &mut W, c_ty: &str, inouts: &[Port])
+/// Write the method setting `port` on the Rust model.
+///
+/// With `checked`, values which do not fit in the width of the port panic;
+/// otherwise the shim truncates them.
+fn write_rs_setter<W>(rs_out: &mut W, c_ty: &str, port: &Port, checked: bool)
 where
     W: Write,
 {
-    for inout in inouts {
+    let check = match port.check() {
+        Some(ref check) if checked => format!("\n        {}", check),
+        _ => String::new(),
+    };
+    let v = if port.words().is_some() {
+        "v.as_ptr()".to_string()
+    } else if port.signed() {
+        format!("v as {}", port.raw_ty())
+    } else {
+        "v".to_string()
+    };
+    t!(writeln!(
+        rs_out,
+        r#"    pub fn set_{port}(&mut self, v: {ty}) {{{check}
+        unsafe {{ ffi::{c_ty}_set_{port}(self.ptr, {v}); }}
+    }}
+"#,
+        c_ty = c_ty,
+        port = port.name,
+        ty = &port.ty,
+        check = check,
+        v = v
+    ));
+}
+
+/// Write the method reading `port` from the Rust model.
+///
+/// Signed values are sign-extended from the width of the port.
+fn write_rs_getter<W>(rs_out: &mut W, c_ty: &str, port: &Port)
+where
+    W: Write,
+{
+    if let Some(words) = port.words() {
         t!(writeln!(
             rs_out,
-            r#"        pub fn {c_ty}_set_{inout}({c_ty}: *mut {c_ty}, v: {ffi_ty});
-        pub fn {c_ty}_get_{inout}({c_ty}: *mut {c_ty}) -> {ffi_ty};"#,
+            r#"    pub fn {port}(&self) -> {ty} {{
+        let mut v = [0; {words}];
+        unsafe {{ ffi::{c_ty}_get_{port}(self.ptr, v.as_mut_ptr()); }}
+        v
+    }}
+"#,
             c_ty = c_ty,
-            inout = inout.name,
-            ffi_ty = rust2ffi(&inout.ty)
+            port = port.name,
+            ty = &port.ty,
+            words = words
         ));
-
+    } else if port.signed() && port.width < port.bits() {
         t!(writeln!(
-            cpp_out,
-            r#"  void
-  {c_ty}_set_{inout}(V{c_ty}* __ptr, {v_ty} __v) {{
-    __ptr->{inout} = __v;
-  }}
-
-  {v_ty}
-  {c_ty}_get_{inout}(V{c_ty}* __ptr) {{
-    return __ptr->{inout};
-  }}
+            rs_out,
+            r#"    pub fn {port}(&self) -> {ty} {{
+        let v = unsafe {{ ffi::{c_ty}_get_{port}(self.ptr) }} as {ty};
+        (v << {shift}) >> {shift}
+    }}
+"#,
+            c_ty = c_ty,
+            port = port.name,
+            ty = &port.ty,
+            shift = port.bits() - port.width
+        ));
+    } else if port.signed() {
+        t!(writeln!(
+            rs_out,
+            r#"    pub fn {port}(&self) -> {ty} {{
+        unsafe {{ ffi::{c_ty}_get_{port}(self.ptr) as {ty} }}
+    }}
 "#,
             c_ty = c_ty,
-            inout = inout.name,
-            v_ty = rust2ver(&inout.ty)
+            port = port.name,
+            ty = &port.ty
+        ));
+    } else {
+        t!(writeln!(
+            rs_out,
+            r#"    pub fn {port}(&self) -> {ty} {{
+        unsafe {{ ffi::{c_ty}_get_{port}(self.ptr) }}
+    }}
+"#,
+            c_ty = c_ty,
+            port = port.name,
+            ty = &port.ty
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(item: &str) -> Ports {
+        extract_ports(&syn::parse_str::<ItemStruct>(item).unwrap().fields).unwrap()
+    }
+
+    fn port(name: &str, direction: Direction, width: usize) -> VerilogPort {
+        VerilogPort {
+            name: name.to_string(),
+            direction,
+            width,
+            signed: false,
+        }
+    }
+
+    /// Check the struct `item` against the ports of `top`.
+    fn check(item: &str, verilog: &[VerilogPort]) -> Result<Vec<String>, ModuleError> {
+        let mut generator = ModuleGenerator::default();
+        generator.with_context(false).with_trace_vcd(false);
+        let out_dir = PathBuf::from("out");
+        let gen = generator.generator(&out_dir, Path::new("src/lib.rs"), Some(("top", verilog)));
+        gen.check_ports("Top", &ports(item))
+    }
+
+    /// The error extracting the ports of the struct `item`.
+    fn field_error(item: &str) -> String {
+        let generator = ModuleGenerator::default();
+        let out_dir = PathBuf::from("out");
+        let gen = generator.generator(&out_dir, Path::new("src/lib.rs"), None);
+        match extract_ports(&syn::parse_str::<ItemStruct>(item).unwrap().fields) {
+            Ok(..) => panic!("expected an invalid port"),
+            Err(e) => gen.field_error(e).to_string(),
+        }
+    }
+
+    fn top() -> Vec<VerilogPort> {
+        vec![
+            port("clk_i", Direction::Input, 1),
+            port("rst_i", Direction::Input, 1),
+            port("count_o", Direction::Output, 4),
+        ]
+    }
+
+    fn mismatches(result: Result<Vec<String>, ModuleError>) -> Vec<String> {
+        match result {
+            Err(ModuleError::PortMismatch { rs_ty, top, errors }) => {
+                assert_eq!((rs_ty.as_str(), top.as_str()), ("Top", "top"));
+                errors
+            }
+            other => panic!("expected a port mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn matching_ports() {
+        let item = "pub struct Top {
+            #[port(clock)] pub clk_i: bool,
+            #[port(reset)] pub rst_i: bool,
+            #[port(output)] pub count_o: [bool; 4],
+        }";
+        assert!(check(item, &top()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unknown_field() {
+        let item = "pub struct Top {
+            #[port(clock)] pub clk_i: bool,
+            #[port(reset)] pub rst_i: bool,
+            #[port(output)] pub count_o: [bool; 4],
+            #[port(input)] pub enable_i: bool,
+        }";
+        assert_eq!(
+            mismatches(check(item, &top())),
+            ["src/lib.rs:5: `enable_i` is not a port of `top`"]
+        );
+    }
+
+    #[test]
+    fn direction_mismatch() {
+        let item = "pub struct Top {
+            #[port(clock)] pub clk_i: bool,
+            #[port(reset)] pub rst_i: bool,
+            #[port(input)] pub count_o: [bool; 4],
+        }";
+        assert_eq!(
+            mismatches(check(item, &top())),
+            ["src/lib.rs:4: `count_o` is declared an input, but is an output of `top`"]
+        );
+    }
+
+    #[test]
+    fn width_mismatch() {
+        let item = "pub struct Top {
+            #[port(clock)] pub clk_i: bool,
+            #[port(reset)] pub rst_i: bool,
+            #[port(output)] pub count_o: [bool; 8],
+        }";
+        assert_eq!(
+            mismatches(check(item, &top())),
+            ["src/lib.rs:4: `count_o` is declared 8 bits wide, but is 4 bits wide in `top`"]
+        );
+    }
+
+    #[test]
+    fn missing_input() {
+        let item = "pub struct Top {
+            #[port(clock)] pub clk_i: bool,
+        }";
+        assert_eq!(
+            check(item, &top()).unwrap(),
+            ["input `rst_i` of `top` has no field in `Top`"]
+        );
+    }
+
+    /// The Rust and C++ accessors of the inputs and outputs of `item`.
+    fn accessors(item: &str, checked: bool) -> (String, String) {
+        let ports = ports(item);
+        let (mut rs, mut cpp) = (Vec::new(), Vec::new());
+        for port in &ports.inputs {
+            write_setter(&mut rs, &mut cpp, "top", port);
+            write_rs_setter(&mut rs, "top", port, checked);
+        }
+        for port in &ports.outputs {
+            write_getter(&mut rs, &mut cpp, "top", port);
+            write_rs_getter(&mut rs, "top", port);
+        }
+        (
+            String::from_utf8(rs).unwrap(),
+            String::from_utf8(cpp).unwrap(),
+        )
+    }
+
+    #[test]
+    fn wide_ports() {
+        let item = "pub struct Top {
+            #[port(input)] pub data_i: [bool; 70],
+            #[port(output)] pub data_o: [bool; 96],
+        }";
+        let (rs, cpp) = accessors(item, false);
+
+        assert!(
+            rs.contains("pub fn top_set_data_i(top: *mut top, v: *const ::std::os::raw::c_uint);")
+        );
+        assert!(rs.contains("pub fn set_data_i(&mut self, v: [u32; 3]) {\n        unsafe"));
+        assert!(rs.contains("ffi::top_set_data_i(self.ptr, v.as_ptr());"));
+        assert!(rs.contains("pub fn data_o(&self) -> [u32; 3] {\n        let mut v = [0; 3];"));
+
+        // Only the top word of the 70 bit port has unused bits to clear.
+        assert!(cpp
+            .contains("for (int __i = 0; __i < 3; ++__i) {\n      __ptr->data_i[__i] = __v[__i];"));
+        assert!(cpp.contains("    __ptr->data_i[2] &= 0x3fU;"));
+        assert!(cpp.contains("__v[__i] = __ptr->data_o[__i];\n    }\n  }"));
+        assert!(!cpp.contains("__v[2] &="));
+    }
+
+    #[test]
+    fn signed_ports() {
+        let item = "pub struct Top {
+            #[port(input, signed)] pub offset_i: [bool; 12],
+            #[port(output, width = 12)] pub offset_o: i16,
+            #[port(output)] pub sum_o: i32,
+            #[port(output, width = 4)] pub count_o: u8,
+        }";
+        let ports = ports(item);
+        assert_eq!(ports.inputs[0].ty, "i16");
+        assert_eq!(ports.inputs[0].width, 12);
+        assert_eq!(ports.outputs[0].ty, "i16");
+        assert_eq!(ports.outputs[0].width, 12);
+
+        let (rs, cpp) = accessors(item, false);
+        assert!(rs.contains("pub fn top_set_offset_i(top: *mut top, v: ::std::os::raw::c_ushort);"));
+        assert!(rs.contains("ffi::top_set_offset_i(self.ptr, v as u16);"));
+        assert!(cpp.contains("__ptr->offset_i = __v & 0xfffULL;"));
+
+        // Narrow signed ports are sign extended from their width, full width
+        // ones only reinterpreted.
+        assert!(rs.contains(
+            "let v = unsafe { ffi::top_get_offset_o(self.ptr) } as i16;\n        (v << 4) >> 4"
         ));
+        assert!(rs.contains("unsafe { ffi::top_get_sum_o(self.ptr) as i32 }"));
+        assert!(rs.contains("unsafe { ffi::top_get_count_o(self.ptr) }\n"));
+    }
+
+    #[test]
+    fn checked_ports() {
+        let item = "pub struct Top {
+            #[port(input)] pub count_i: [bool; 4],
+            #[port(input, signed)] pub offset_i: [bool; 12],
+            #[port(input)] pub data_i: [bool; 70],
+            #[port(input)] pub word_i: u32,
+        }";
+
+        // Unchecked setters leave the shim to truncate the value.
+        let (rs, cpp) = accessors(item, false);
+        assert!(!rs.contains("assert!"));
+        assert!(cpp.contains("__ptr->count_i = __v & 0xfULL;"));
+        assert!(cpp.contains("__ptr->data_i[2] &= 0x3fU;"));
+
+        let (rs, _) = accessors(item, true);
+        assert!(rs.contains(
+            r#"assert!(v <= 0xf, "value {:#x} does not fit in the 4 bit port `count_i`", v);"#
+        ));
+        assert!(rs.contains(
+            r#"assert!((-2048..=2047).contains(&v), "value {} does not fit in the 12 bit port `offset_i`", v);"#
+        ));
+        assert!(rs.contains(
+            r#"assert!(v[2] >> 6 == 0, "value {:x?} does not fit in the 70 bit port `data_i`", v);"#
+        ));
+        assert!(rs.contains("pub fn set_word_i(&mut self, v: u32) {\n        unsafe"));
+    }
+
+    #[test]
+    fn invalid_fields() {
+        assert_eq!(
+            field_error(
+                "pub struct Top {
+                    #[port(clock)] pub clk_i: bool,
+                    #[port(input, width = 40)] pub data_i: u32,
+                }"
+            ),
+            "invalid port: src/lib.rs:3: port of width 40 does not fit in `u32`"
+        );
+        assert_eq!(
+            field_error("pub struct Top { #[port(input, signed)] pub data_i: u8 }"),
+            "invalid port: src/lib.rs:1: signed ports must be declared `i8`, not `u8`"
+        );
+        assert_eq!(
+            field_error("pub struct Top { #[port(input, width = \"4\")] pub data_i: u8 }"),
+            "invalid port: src/lib.rs:1: expected an integer width"
+        );
+        assert_eq!(
+            field_error("pub struct Top { #[port(input, output)] pub data_i: u8 }"),
+            "invalid port: src/lib.rs:1: expected one of clock, reset, input, output or inout"
+        );
+        assert_eq!(
+            field_error("pub struct Top { #[port(input, signed)] pub data_i: [bool; 70] }"),
+            "invalid port: src/lib.rs:1: signed ports wider than 64 bits are not supported"
+        );
+        assert_eq!(
+            field_error("pub struct Top { #[port(output)] pub data_o: [u8; 4] }"),
+            "invalid port: src/lib.rs:1: only arrays of bool are supported"
+        );
+        assert_eq!(
+            field_error("pub struct Top { #[port(output)] pub data_o: [bool; WIDTH] }"),
+            "invalid port: src/lib.rs:1: expected an integer array length"
+        );
     }
 }
@@ -0,0 +1,371 @@
+//! Extraction of the ports of a top module from the netlist written by
+//! `verilator --xml-only`.
+
+use fnv::FnvHashMap;
+use roxmltree::{Document, Node, ParsingOptions};
+use std::fmt;
+
+/// The direction of a Verilog port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+    InOut,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Direction::Input => write!(f, "input"),
+            Direction::Output => write!(f, "output"),
+            Direction::InOut => write!(f, "inout"),
+        }
+    }
+}
+
+/// A port of the top module.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerilogPort {
+    pub name: String,
+    pub direction: Direction,
+    pub width: usize,
+    pub signed: bool,
+}
+
+/// The data types of the type table by id.
+type DTypes<'a, 'input> = FnvHashMap<&'a str, Node<'a, 'input>>;
+
+/// Return the ports of the module `top` in `xml`, in declaration order.
+pub fn parse(xml: &str, top: &str) -> Result<Vec<VerilogPort>, String> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    let doc =
+        Document::parse_with_options(xml, options).map_err(|e| format!("invalid XML: {}", e))?;
+    let module = doc
+        .descendants()
+        .find(|n| n.has_tag_name("module") && n.attribute("name") == Some(top))
+        .ok_or_else(|| format!("module `{}` not found", top))?;
+    let dtypes: DTypes = doc
+        .descendants()
+        .filter(|n| n.tag_name().name().ends_with("dtype"))
+        .filter_map(|n| Some((n.attribute("id")?, n)))
+        .collect();
+
+    module
+        .children()
+        .filter(|n| n.has_tag_name("var"))
+        .filter_map(|var| Some((var.attribute("name")?, var.attribute("dir")?, var)))
+        .map(|(name, dir, var)| {
+            let direction = match dir {
+                "input" => Direction::Input,
+                "output" => Direction::Output,
+                "inout" => Direction::InOut,
+                _ => {
+                    return Err(format!(
+                        "port `{}` has unsupported direction `{}`",
+                        name, dir
+                    ))
+                }
+            };
+            let dtype = match var.attribute("dtype_id").and_then(|id| dtypes.get(id)) {
+                Some(&dtype) => dtype,
+                None => return Err(format!("port `{}` has no type", name)),
+            };
+            let width = match width(&dtypes, dtype, 0) {
+                Some(width) => width,
+                None => return Err(format!("port `{}` has an unsupported type", name)),
+            };
+            Ok(VerilogPort {
+                name: name.to_string(),
+                direction,
+                width,
+                signed: signed(&dtypes, dtype, 0),
+            })
+        })
+        .collect()
+}
+
+/// Data types refer to others at most this deep, which guards against
+/// cycles.
+const MAX_DEPTH: usize = 64;
+
+/// The width in bits of the packed data type `dtype`, resolving the types
+/// it refers to.
+fn width(dtypes: &DTypes, dtype: Node, depth: usize) -> Option<usize> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let sub = || {
+        let sub = dtypes.get(dtype.attribute("sub_dtype_id")?)?;
+        width(dtypes, *sub, depth + 1)
+    };
+    let bound = |attr: &str| dtype.attribute(attr)?.parse().ok();
+    match dtype.tag_name().name() {
+        "basicdtype" => basic_width(dtype),
+        "refdtype" | "typedefdtype" | "enumdtype" | "memberdtype" | "constdtype" => sub(),
+        "packarraydtype" => {
+            let (left, right) = match (bound("left"), bound("right")) {
+                (Some(left), Some(right)) => (left, right),
+                // Otherwise the bounds are the constants of its range.
+                _ => {
+                    let range = dtype.children().find(|n| n.has_tag_name("range"))?;
+                    let bounds: Vec<_> = range
+                        .children()
+                        .filter(|n| n.has_tag_name("const"))
+                        .map(|n| n.attribute("name").and_then(parse_const))
+                        .collect::<Option<_>>()?;
+                    match bounds[..] {
+                        [left, right] => (left, right),
+                        _ => return None,
+                    }
+                }
+            };
+            Some(range_width(left, right) * sub()?)
+        }
+        kind @ "structdtype" | kind @ "uniondtype" => {
+            let widths = dtype
+                .children()
+                .filter(|n| n.tag_name().name().ends_with("dtype"))
+                .map(|m| width(dtypes, m, depth + 1))
+                .collect::<Option<Vec<_>>>()?;
+            if kind == "structdtype" {
+                Some(widths.iter().sum())
+            } else {
+                widths.into_iter().max()
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether the data type `dtype` is signed. Arrays, structs and unions are
+/// signed only when declared so.
+fn signed(dtypes: &DTypes, dtype: Node, depth: usize) -> bool {
+    if depth > MAX_DEPTH {
+        return false;
+    }
+    match dtype.tag_name().name() {
+        "refdtype" | "typedefdtype" | "enumdtype" | "memberdtype" | "constdtype" => dtype
+            .attribute("sub_dtype_id")
+            .and_then(|sub| dtypes.get(sub))
+            .is_some_and(|&sub| signed(dtypes, sub, depth + 1)),
+        _ => dtype.attribute("signed") == Some("true"),
+    }
+}
+
+/// The width of a `<basicdtype>`, from its range or, without one, its
+/// keyword.
+fn basic_width(dtype: Node) -> Option<usize> {
+    match (dtype.attribute("left"), dtype.attribute("right")) {
+        (Some(left), Some(right)) => Some(range_width(left.parse().ok()?, right.parse().ok()?)),
+        _ => match dtype.attribute("name") {
+            Some("logic") | Some("bit") | Some("wire") | Some("reg") => Some(1),
+            Some("byte") => Some(8),
+            Some("shortint") => Some(16),
+            Some("int") | Some("integer") => Some(32),
+            Some("longint") => Some(64),
+            _ => None,
+        },
+    }
+}
+
+fn range_width(left: i64, right: i64) -> usize {
+    (left - right).unsigned_abs() as usize + 1
+}
+
+/// Parse a constant as written by verilator, such as `32'h1f`, `32'sh1f`
+/// or `7`.
+fn parse_const(s: &str) -> Option<i64> {
+    let value = match s.find('\'') {
+        Some(pos) => s[pos + 1..].trim_start_matches('s'),
+        None => return s.parse().ok(),
+    };
+    let radix = match value.chars().next()? {
+        'h' => 16,
+        'd' => 10,
+        'o' => 8,
+        'b' => 2,
+        _ => return None,
+    };
+    i64::from_str_radix(&value[1..].replace('_', ""), radix).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn port(name: &str, direction: Direction, width: usize, signed: bool) -> VerilogPort {
+        VerilogPort {
+            name: name.to_string(),
+            direction,
+            width,
+            signed,
+        }
+    }
+
+    const NETLIST: &str = r#"<?xml version="1.0" ?>
+<verilator_xml>
+  <netlist>
+    <module loc="d,1,8,1,11" name="top" origName="top" topModule="1">
+      <var loc="d,2,15,2,20" name="clk_i" dtype_id="1" dir="input" pinIndex="1" vartype="logic" origName="clk_i"/>
+      <var loc="d,3,29,3,32" name="a_i" dtype_id="2" dir="input" pinIndex="2" vartype="logic" origName="a_i"/>
+      <var loc="d,4,18,4,21" name="n_o" dtype_id="3" dir="output" pinIndex="3" vartype="int" origName="n_o"/>
+      <var loc="d,5,22,5,26" name="f_io" dtype_id="4" dir="inout" pinIndex="4" vartype="logic" origName="f_io"/>
+      <var loc="d,6,22,6,25" name="tmp" dtype_id="4" vartype="logic" origName="tmp"/>
+      <instance loc="d,8,11,8,14" name="u_sub" defName="sub" origName="u_sub"/>
+    </module>
+    <module loc="d,10,8,10,11" name="sub" origName="sub">
+      <var loc="d,11,15,11,18" name="clk" dtype_id="1" dir="input" pinIndex="1" vartype="logic" origName="clk"/>
+    </module>
+    <typetable loc="a,0,0,0,0">
+      <basicdtype loc="d,2,15,2,20" id="1" name="logic"/>
+      <basicdtype loc="d,3,14,3,17" id="2" name="logic" left="11" right="0" signed="true"/>
+      <basicdtype loc="d,4,14,4,17" id="3" name="int" left="31" right="0" signed="true"/>
+      <basicdtype loc="d,5,14,5,17" id="4" name="logic" left="0" right="4"/>
+    </typetable>
+  </netlist>
+</verilator_xml>
+"#;
+
+    #[test]
+    fn ports() {
+        assert_eq!(
+            parse(NETLIST, "top"),
+            Ok(vec![
+                port("clk_i", Direction::Input, 1, false),
+                port("a_i", Direction::Input, 12, true),
+                port("n_o", Direction::Output, 32, true),
+                port("f_io", Direction::InOut, 5, false),
+            ])
+        );
+        assert_eq!(
+            parse(NETLIST, "sub"),
+            Ok(vec![port("clk", Direction::Input, 1, false)])
+        );
+    }
+
+    #[test]
+    fn missing_top() {
+        assert_eq!(
+            parse(NETLIST, "counter"),
+            Err("module `counter` not found".to_string())
+        );
+    }
+
+    #[test]
+    fn self_closing_module() {
+        let xml = r#"<netlist>
+  <module loc="d,1,8,1,11" name="top" origName="top" topModule="1"/>
+  <module loc="d,2,8,2,11" name="sub" origName="sub">
+    <var loc="d,3,15,3,18" name="clk" dtype_id="1" dir="input" vartype="logic"/>
+  </module>
+  <typetable><basicdtype id="1" name="logic"/></typetable>
+</netlist>"#;
+        assert_eq!(parse(xml, "top"), Ok(vec![]));
+    }
+
+    #[test]
+    fn typedefs_arrays_and_structs() {
+        let xml = r#"<netlist>
+  <module loc="d,1,8,1,11" name="top" origName="top" topModule="1">
+    <var loc="d,2,15,2,20" name="word_i" dtype_id="10" dir="input" vartype="word_t"/>
+    <var loc="d,3,15,3,20" name="bytes_o" dtype_id="11" dir="output" vartype=""/>
+    <var loc="d,4,15,4,20" name="pkt_o" dtype_id="12" dir="output" vartype="pkt_t"/>
+    <var loc="d,5,15,5,20" name="state_o" dtype_id="14" dir="output" vartype="state_t"/>
+  </module>
+  <typetable loc="a,0,0,0,0">
+    <basicdtype loc="d,1,1,1,1" id="1" name="logic"/>
+    <basicdtype loc="d,1,1,1,1" id="2" name="logic" left="15" right="0" signed="true"/>
+    <basicdtype loc="d,1,1,1,1" id="3" name="logic" left="7" right="0"/>
+    <refdtype loc="d,2,9,2,15" id="10" name="word_t" sub_dtype_id="2"/>
+    <packarraydtype loc="d,3,9,3,15" id="11" sub_dtype_id="3">
+      <range loc="d,3,10,3,11">
+        <const loc="d,3,10,3,11" name="32&apos;h3" dtype_id="5"/>
+        <const loc="d,3,12,3,13" name="32&apos;h0" dtype_id="5"/>
+      </range>
+    </packarraydtype>
+    <structdtype loc="d,4,9,4,15" id="12" name="top::pkt_t">
+      <memberdtype loc="d,4,20,4,24" id="20" name="valid" sub_dtype_id="1"/>
+      <memberdtype loc="d,4,30,4,34" id="21" name="data" sub_dtype_id="10"/>
+    </structdtype>
+    <enumdtype loc="d,5,9,5,15" id="14" name="top::state_t" sub_dtype_id="15">
+      <enumitem loc="d,5,20,5,24" name="IDLE" dtype_id="15"/>
+    </enumdtype>
+    <basicdtype loc="d,1,1,1,1" id="15" name="logic" left="1" right="0"/>
+  </typetable>
+</netlist>"#;
+        assert_eq!(
+            parse(xml, "top"),
+            Ok(vec![
+                port("word_i", Direction::Input, 16, true),
+                port("bytes_o", Direction::Output, 32, false),
+                port("pkt_o", Direction::Output, 17, false),
+                port("state_o", Direction::Output, 2, false),
+            ])
+        );
+    }
+
+    #[test]
+    fn unsupported_types() {
+        let xml = r#"<netlist>
+  <module name="top">
+    <var name="mem_i" dtype_id="2" dir="input"/>
+    <var name="r_i" dtype_id="3" dir="input"/>
+    <var name="x_i" dtype_id="9" dir="input"/>
+  </module>
+  <typetable>
+    <basicdtype id="1" name="logic"/>
+    <unpackarraydtype id="2" sub_dtype_id="1"/>
+    <basicdtype id="3" name="real"/>
+  </typetable>
+</netlist>"#;
+        assert_eq!(
+            parse(xml, "top"),
+            Err("port `mem_i` has an unsupported type".to_string())
+        );
+        let xml = xml.replace(r#"dtype_id="2" dir"#, r#"dtype_id="1" dir"#);
+        assert_eq!(
+            parse(&xml, "top"),
+            Err("port `r_i` has an unsupported type".to_string())
+        );
+        let xml = xml.replace(r#"dtype_id="3" dir"#, r#"dtype_id="1" dir"#);
+        assert_eq!(
+            parse(&xml, "top"),
+            Err("port `x_i` has no type".to_string())
+        );
+    }
+
+    #[test]
+    fn comments_and_markup() {
+        let xml = r#"<?xml version="1.0" ?>
+<!-- DESCRIPTION: Verilator output: XML representation of netlist -->
+<verilator_xml>
+  <files><file id="d" filename="a&gt;b.v" language="1800-2017"/></files>
+  <netlist>
+    <!-- <module name="top"><var name="old_i" dtype_id="1" dir="input"/></module> -->
+    <module name="top" origName="a&gt;b">
+      <var name="clk_i" dtype_id="1" dir="input"/>
+    </module>
+    <typetable><basicdtype id="1" name="logic"/></typetable>
+  </netlist>
+</verilator_xml>"#;
+        assert_eq!(
+            parse(xml, "top"),
+            Ok(vec![port("clk_i", Direction::Input, 1, false)])
+        );
+        assert!(parse("<netlist><module name=\"top\">", "top")
+            .unwrap_err()
+            .starts_with("invalid XML: "));
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(parse_const("32'h1f"), Some(31));
+        assert_eq!(parse_const("32'sh1f"), Some(31));
+        assert_eq!(parse_const("8'b1010_0000"), Some(160));
+        assert_eq!(parse_const("7"), Some(7));
+        assert_eq!(parse_const("32'hx"), None);
+    }
+}